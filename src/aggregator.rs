@@ -1,8 +1,89 @@
+use serde::Serialize;
+use solana_sdk::clock::Slot;
+use solana_sdk::commitment_config::CommitmentLevel;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::sleep;
 use tokio_util::sync::CancellationToken;
 use crate::source::{SourceEvent, SourceStream};
 use crate::error::{Error, Result};
 use crate::storage::StorageInterface;
-use crate::types::Block;
+use crate::types::{Block, BlockSummary, Hash};
+
+/// The number of recent blocks kept to roll back to if the ingested blocks are not finalized, which
+/// is about the distance between the confirmed and the finalized slot.
+const MAX_ROLLBACK_DEPTH: usize = 64;
+
+/// The delay before the source is rewound to fetch the blocks after a gap or a fork again, which
+/// doubles with every retry up to `MAX_RETRY_DELAY` until a block was processed again.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Publishes every stored block to the API subscribers.
+pub type BlockSender = broadcast::Sender<Arc<BlockSummary>>;
+
+/// Emitted when a new block doesn't link to the last stored block.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ChainEvent {
+    /// The parent of the new block is ahead of the last stored block, there are blocks missing in
+    /// between.
+    Gap {
+        last_slot: Slot,
+        parent_slot: Slot,
+        slot: Slot,
+    },
+    /// The new block builds on a different block than the last stored one.
+    Fork {
+        last_slot: Slot,
+        last_hash: Hash,
+        parent_slot: Slot,
+        previous_blockhash: Hash,
+        slot: Slot,
+    },
+}
+
+impl ChainEvent {
+    /// The slot of the last processed block which the new block doesn't link to.
+    pub fn last_slot(&self) -> Slot {
+        match self {
+            ChainEvent::Gap {last_slot, ..} | ChainEvent::Fork {last_slot, ..} => *last_slot,
+        }
+    }
+
+    /// Verifies that `block` is the direct child of the block with `last_slot` and `last_hash`.
+    pub fn verify(last_slot: Slot, last_hash: &Hash, block: &Block) -> Option<ChainEvent> {
+        if block.parent_slot == last_slot && &block.previous_blockhash == last_hash {
+            return None;
+        }
+        if block.parent_slot > last_slot {
+            return Some(ChainEvent::Gap {
+                last_slot,
+                parent_slot: block.parent_slot,
+                slot: block.slot,
+            });
+        }
+        Some(ChainEvent::Fork {
+            last_slot,
+            last_hash: last_hash.clone(),
+            parent_slot: block.parent_slot,
+            previous_blockhash: block.previous_blockhash.clone(),
+            slot: block.slot,
+        })
+    }
+}
+
+impl fmt::Display for ChainEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match serde_json::to_string(self) {
+            Ok(json) => write!(f, "{}", json),
+            Err(_) => write!(f, "{:?}", self),
+        }
+    }
+}
 
 pub struct Aggregator<Source>
 where
@@ -10,18 +91,39 @@ where
 {
     source: Source,
     storage: StorageInterface,
+    /// Receives every stored block for the API subscribers.
+    blocks: BlockSender,
     token: CancellationToken,
+    commitment: CommitmentLevel,
+    recent_blocks: VecDeque<(Slot, Hash)>,
+    /// The number of retries since the last processed block.
+    retries: u32,
 }
 
 impl <Source> Aggregator<Source>
 where
     Source: SourceStream,
 {
-    pub fn new(source: Source, storage: StorageInterface, token: CancellationToken) -> Self {
-        Self {source, storage, token}
+    pub fn new(
+        source: Source,
+        storage: StorageInterface,
+        blocks: BlockSender,
+        token: CancellationToken,
+        commitment: CommitmentLevel,
+    ) -> Self {
+        Self {source, storage, blocks, token, commitment, recent_blocks: VecDeque::new(), retries: 0}
     }
 
     pub async fn run(&mut self) {
+        match self.storage.get_last_block().await {
+            Ok(last_block) => {
+                self.recent_blocks.extend(last_block.map(|block| (block.slot, block.hash)));
+            }
+            Err(error) => {
+                log::error!("Failed to fetch the last block: {}", error);
+                return
+            }
+        }
         loop {
             if self.token.is_cancelled() {
                 log::debug!("run() interrupted");
@@ -30,7 +132,7 @@ where
             let event = self.source.next().await;
             match event {
                 SourceEvent::Next(block) => {
-                    match self.process_block(block).await {
+                    match self.process_block(*block).await {
                         Ok(_) => {continue}
                         Err(error) => {
                             log::error!("Failed to process block: {}", error);
@@ -55,9 +157,285 @@ where
     }
 
     async fn process_block(&mut self, block: Block) -> Result<()> {
-        if block.transactions.len() != 0 {
+        let event = self.recent_blocks.back().and_then(|(last_slot, last_hash)| {
+            ChainEvent::verify(*last_slot, last_hash, &block)
+        });
+        if let Some(event) = event {
+            log::warn!("Chain event: {}", event);
+            if !self.rollback(&event, &block).await? {
+                if self.retry(event.last_slot()).await {
+                    return Ok(());
+                }
+                // Without another chance to fetch them, the missing blocks stay a hole in the storage.
+                if let ChainEvent::Gap {last_slot, parent_slot, ..} = event {
+                    log::error!("Skipped the missing blocks from slot {} to {}", last_slot + 1, parent_slot);
+                } else {
+                    log::error!("Dropped the block of slot {} which builds on an unknown fork", block.slot);
+                    return Ok(());
+                }
+            }
+        }
+        let last_block = (block.slot, block.hash.clone());
+        // Blocks without content are not stored, the chain check only needs their slot and hash.
+        if !block.is_empty() {
+            // The block is only summarized if there is anyone subscribed to it.
+            let summary = (self.blocks.receiver_count() > 0).then(|| Arc::new(BlockSummary::from(block.clone())));
             self.storage.add_block(block).await?;
+            if let Some(summary) = summary {
+                // Fails only if all subscribers disconnected in the meantime.
+                let _ = self.blocks.send(summary);
+            }
+        }
+        self.recent_blocks.push_back(last_block);
+        self.retries = 0;
+        let depth = if self.commitment == CommitmentLevel::Finalized { 1 } else { MAX_ROLLBACK_DEPTH };
+        while self.recent_blocks.len() > depth {
+            self.recent_blocks.pop_front();
         }
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Rewinds the source to the slot after `last_slot` to fetch the blocks after it again, the
+    /// block which didn't link is dropped. Waits longer with every retry, so a block which is missing
+    /// on the RPC node for a while doesn't flood it with requests. Returns `false` if the source
+    /// can't rewind.
+    async fn retry(&mut self, last_slot: Slot) -> bool {
+        if !self.source.rewind(last_slot + 1) {
+            return false;
+        }
+        let delay = RETRY_DELAY.saturating_mul(2u32.saturating_pow(self.retries)).min(MAX_RETRY_DELAY);
+        self.retries = self.retries.saturating_add(1);
+        log::warn!("Retry from slot {} in {:?}, attempt {}", last_slot + 1, delay, self.retries);
+        tokio::select! {
+            _ = sleep(delay) => {}
+            _ = self.token.cancelled() => {}
+        }
+        true
+    }
+
+    /// Rolls the storage back to the parent of `block` if the `event` is a fork of the not yet
+    /// finalized recent blocks. Returns `false` if there was nothing to roll back to.
+    async fn rollback(&mut self, event: &ChainEvent, block: &Block) -> Result<bool> {
+        if self.commitment == CommitmentLevel::Finalized || !matches!(event, ChainEvent::Fork {..}) {
+            return Ok(false);
+        }
+        let parent = self.recent_blocks.iter().position(|(slot, hash)| {
+            *slot == block.parent_slot && hash == &block.previous_blockhash
+        });
+        let Some(parent) = parent else {
+            return Ok(false);
+        };
+        self.storage.rollback_to(block.parent_slot).await?;
+        log::warn!(
+            "Rolled back {} blocks to slot {}",
+            self.recent_blocks.len() - parent - 1,
+            block.parent_slot
+        );
+        self.recent_blocks.truncate(parent + 1);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::Memory;
+    use crate::storage::Storage;
+    use crate::types::{SystemInstructionKind, Transaction};
+    use solana_sdk::pubkey::Pubkey;
+    use tokio::sync::mpsc;
+
+    fn sender() -> String {
+        Pubkey::new_from_array([1; 32]).to_string()
+    }
+
+    fn get_block(slot: Slot, parent_slot: Slot, previous_blockhash: &str) -> Block {
+        Block {
+            slot,
+            parent_slot,
+            height: slot,
+            hash: slot.to_string(),
+            previous_blockhash: previous_blockhash.to_string(),
+            timestamp: 0,
+            // Blocks without any content are not stored.
+            transactions: Vec::from([Transaction {
+                signature: slot.to_string(),
+                instruction_index: 0,
+                invocation: None,
+                kind: SystemInstructionKind::Transfer,
+                sender: sender(),
+                receiver: Pubkey::new_from_array([2; 32]).to_string(),
+                amount: 0,
+            }]),
+            balance_changes: Vec::new(),
+            transaction_metas: Vec::new(),
+            token_transfers: Vec::new(),
+            token_balance_changes: Vec::new(),
+            raw_instructions: Vec::new(),
+            custom_instructions: Vec::new(),
+            stake_instructions: Vec::new(),
+            votes: Vec::new(),
+            rewards: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_verify_chain() {
+        let last_hash = "10".to_string();
+        // Direct child, also if slots were skipped in between.
+        assert_eq!(ChainEvent::verify(10, &last_hash, &get_block(11, 10, "10")), None);
+        assert_eq!(ChainEvent::verify(10, &last_hash, &get_block(13, 10, "10")), None);
+        // Missing blocks between the last and the new block.
+        assert_eq!(
+            ChainEvent::verify(10, &last_hash, &get_block(13, 12, "12")),
+            Some(ChainEvent::Gap {last_slot: 10, parent_slot: 12, slot: 13})
+        );
+        // Same parent slot but a different parent hash.
+        assert_eq!(
+            ChainEvent::verify(10, &last_hash, &get_block(11, 10, "other")),
+            Some(ChainEvent::Fork {
+                last_slot: 10,
+                last_hash: last_hash.clone(),
+                parent_slot: 10,
+                previous_blockhash: "other".to_string(),
+                slot: 11,
+            })
+        );
+        // Builds on an older block than the last one.
+        assert_eq!(
+            ChainEvent::verify(10, &last_hash, &get_block(11, 9, "9")),
+            Some(ChainEvent::Fork {
+                last_slot: 10,
+                last_hash: last_hash.clone(),
+                parent_slot: 9,
+                previous_blockhash: "9".to_string(),
+                slot: 11,
+            })
+        );
+    }
+
+    /// Serves the blocks of the first batch in order. Every rewind records the slot and continues
+    /// with the next batch at it, or in the last batch if there is none. Cancels `token` after
+    /// `max_rewinds` rewinds, since a source which is rewound over and over never ends.
+    struct TestSource {
+        batches: VecDeque<Vec<Block>>,
+        position: usize,
+        rewinds: Vec<Slot>,
+        max_rewinds: usize,
+        token: CancellationToken,
+    }
+
+    impl SourceStream for TestSource {
+        async fn next(&mut self) -> SourceEvent {
+            let block = self.batches.front().and_then(|blocks| blocks.get(self.position));
+            self.position += 1;
+            match block {
+                Some(block) => SourceEvent::Next(Box::new(block.clone())),
+                None => SourceEvent::EndOfStream,
+            }
+        }
+
+        fn rewind(&mut self, slot: Slot) -> bool {
+            self.rewinds.push(slot);
+            if self.rewinds.len() == self.max_rewinds {
+                self.token.cancel();
+            }
+            if self.batches.len() > 1 {
+                self.batches.pop_front();
+            }
+            let blocks = &self.batches[0];
+            self.position = blocks.iter().position(|block| block.slot >= slot).unwrap_or(blocks.len());
+            true
+        }
+    }
+
+    /// Runs an aggregator over the `batches` of a [`TestSource`] until the end of the stream or
+    /// `max_rewinds`. Returns the stored slots and the slots the source was rewound to.
+    async fn aggregate(
+        batches: Vec<Vec<Block>>,
+        commitment: CommitmentLevel,
+        max_rewinds: usize,
+    ) -> (Vec<Slot>, Vec<Slot>) {
+        let token = CancellationToken::new();
+        let (storage_tx, storage_rx) = mpsc::channel(20);
+        let storage_token = CancellationToken::new();
+        let run_token = storage_token.clone();
+        tokio::spawn(async move {
+            Memory::default().run(storage_rx, run_token).await
+        });
+        let storage = StorageInterface::new(storage_tx);
+        let source = TestSource {
+            batches: VecDeque::from(batches),
+            position: 0,
+            rewinds: Vec::new(),
+            max_rewinds,
+            token: token.clone(),
+        };
+        let (blocks, _) = broadcast::channel(1);
+        let mut aggregator = Aggregator::new(source, storage.clone(), blocks, token, commitment);
+        aggregator.run().await;
+        let slots = storage.get_blocks(0, 1000).await.unwrap().into_iter().map(|block| block.slot).collect();
+        storage_token.cancel();
+        (slots, aggregator.source.rewinds)
+    }
+
+    /// The blocks of the slots `from` to `to`, each building on the previous one.
+    fn chain(from: Slot, to: Slot) -> Vec<Block> {
+        (from..=to).map(|slot| get_block(slot, slot - 1, &(slot - 1).to_string())).collect()
+    }
+
+    #[tokio::test]
+    async fn test_empty_blocks() {
+        let mut blocks = chain(10, 13);
+        blocks[1].transactions.clear();
+        // The empty block is not stored, but the next one still links to it.
+        assert_eq!(
+            aggregate(Vec::from([blocks]), CommitmentLevel::Finalized, usize::MAX).await,
+            (Vec::from([10, 12, 13]), Vec::new())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gap() {
+        let finalized = CommitmentLevel::Finalized;
+        let with_gap: Vec<Block> = chain(10, 13).into_iter().filter(|block| block.slot != 11).collect();
+        // The source is rewound behind the last stored block and the missing block is fetched again.
+        assert_eq!(
+            aggregate(Vec::from([with_gap.clone(), chain(10, 13)]), finalized, usize::MAX).await,
+            (Vec::from([10, 11, 12, 13]), Vec::from([11]))
+        );
+        // The aggregator keeps retrying a block which stays missing for a while.
+        let batches = Vec::from([with_gap.clone(), with_gap.clone(), with_gap.clone(), chain(10, 13)]);
+        assert_eq!(
+            aggregate(batches, finalized, usize::MAX).await,
+            (Vec::from([10, 11, 12, 13]), Vec::from([11; 3]))
+        );
+        // Nothing after the gap is stored as long as it persists.
+        assert_eq!(aggregate(Vec::from([with_gap]), finalized, 4).await, (Vec::from([10]), Vec::from([11; 4])));
+    }
+
+    #[tokio::test]
+    async fn test_fork() {
+        // A fork of the recent blocks is rolled back without a retry.
+        let mut blocks = chain(10, 12);
+        blocks[2] = get_block(12, 10, "10");
+        assert_eq!(
+            aggregate(Vec::from([blocks]), CommitmentLevel::Confirmed, usize::MAX).await,
+            (Vec::from([10, 12]), Vec::new())
+        );
+        // Finalized blocks can't be rolled back, the aggregator fetches the blocks again and continues.
+        let blocks = Vec::from([get_block(10, 9, "9"), get_block(11, 10, "other")]);
+        assert_eq!(
+            aggregate(Vec::from([blocks, chain(10, 13)]), CommitmentLevel::Finalized, usize::MAX).await,
+            (Vec::from([10, 11, 12, 13]), Vec::from([11]))
+        );
+        // The same for forks deeper than the recent blocks.
+        let depth = MAX_ROLLBACK_DEPTH as Slot;
+        let mut blocks = chain(1, depth + 1);
+        blocks.push(get_block(depth + 2, 1, "1"));
+        assert_eq!(
+            aggregate(Vec::from([blocks, chain(1, depth + 3)]), CommitmentLevel::Confirmed, usize::MAX).await,
+            (Vec::from_iter(1..=depth + 3), Vec::from([depth + 2]))
+        );
+    }
+}
@@ -0,0 +1,753 @@
+pub mod subscribe;
+
+use crate::api::subscribe::{subscribe_sse, subscribe_websocket, SubscribeQuery, Subscriptions};
+use crate::error::{Error, Result as StorageResult};
+use crate::storage::{Direction, MemoFilter, Order, Page, StatusFilter, StorageInterface, TransactionFilter};
+use crate::types::{parse_address, Address, BlockSummary, Signature, SystemInstructionKind, TransactionWithMeta};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use tokio_util::sync::CancellationToken;
+use warp::http::StatusCode;
+use warp::reject::{InvalidHeader, InvalidQuery, MethodNotAllowed, MissingHeader};
+use warp::ws::MissingConnectionUpgrade;
+use warp::reply::{Json, WithStatus};
+use warp::Filter;
+
+const DAY_FORMAT: &str = "%d/%m/%Y";
+/// The maximum number of blocks served by one `/blocks` query.
+const MAX_BLOCK_RANGE: u64 = 100;
+const DEFAULT_PAGE_LIMIT: usize = 100;
+const MAX_PAGE_LIMIT: usize = 1000;
+
+/// The stable codes of the error replies. Clients should match on them instead of the messages.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidRequest,
+    NotFound,
+    MethodNotAllowed,
+    Unavailable,
+    Internal,
+}
+
+impl ErrorCode {
+    fn status(&self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidRequest => StatusCode::BAD_REQUEST,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            ErrorCode::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+/// The body of all error replies.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ErrorReply {
+    pub error: ApiError,
+}
+
+impl ApiError {
+    fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {code, message: message.into()}
+    }
+    fn reply(self) -> WithStatus<Json> {
+        let status = self.code.status();
+        warp::reply::with_status(warp::reply::json(&ErrorReply {error: self}), status)
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        let code = match error {
+            Error::ChannelFailure(..) | Error::Shutdown | Error::RpcError(_) | Error::SlotNotAvailable(_) => {
+                ErrorCode::Unavailable
+            }
+            Error::SlotSkippedOrMissing(_) => ErrorCode::NotFound,
+            Error::UnknownParser(_) => ErrorCode::InvalidRequest,
+            Error::InvalidBlock(..) | Error::SqliteError(_) => ErrorCode::Internal,
+        };
+        if code == ErrorCode::Internal {
+            log::error!("API request failed: {}", error);
+        }
+        ApiError::new(code, error.to_string())
+    }
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoMatch {
+    #[default]
+    Exact,
+    Prefix,
+}
+
+/// The query parameters of `/transactions`, exactly one of `address`, `id`, `day`, `mint` and `memo` is required.
+#[derive(Deserialize)]
+pub struct TransactionQuery {
+    pub address: Option<Address>,
+    pub id: Option<Signature>,
+    pub day: Option<String>,
+    pub mint: Option<Address>,
+    pub status: Option<StatusFilter>,
+    pub kind: Option<SystemInstructionKind>,
+    pub memo: Option<String>,
+    pub memo_match: Option<MemoMatch>,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+    pub order: Option<Order>,
+    /// The earliest block timestamp in seconds since the epoch.
+    pub from: Option<i64>,
+    /// The latest block timestamp in seconds since the epoch.
+    pub to: Option<i64>,
+    pub min_amount: Option<u64>,
+    pub max_amount: Option<u64>,
+    pub direction: Option<Direction>,
+}
+
+impl TransactionQuery {
+    fn filter(&self) -> TransactionFilter {
+        TransactionFilter {
+            from: self.from,
+            to: self.to,
+            min_amount: self.min_amount,
+            max_amount: self.max_amount,
+            direction: self.direction,
+            status: self.status,
+            kind: self.kind,
+        }
+    }
+
+    /// Whether any of the filters which are only supported together with `address` is set.
+    fn has_address_filter(&self) -> bool {
+        self.from.is_some() || self.to.is_some() || self.min_amount.is_some() || self.max_amount.is_some()
+            || self.direction.is_some()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GetAccounts {
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+    pub order: Option<Order>,
+}
+
+/// The items of a paginated list, `next_cursor` is passed as `cursor` to get the next page.
+#[derive(Serialize)]
+pub struct PageReply<Item> {
+    pub items: Vec<Item>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct GetInstructions {
+    pub status: Option<StatusFilter>,
+}
+
+#[derive(Deserialize)]
+pub struct GetBlocks {
+    pub from: u64,
+    pub to: u64,
+}
+
+#[derive(Deserialize)]
+pub struct GetRewards {
+    pub address: Address,
+}
+
+fn bad_request(message: &str) -> WithStatus<Json> {
+    ApiError::new(ErrorCode::InvalidRequest, message).reply()
+}
+
+fn json_reply<T: Serialize>(result: StorageResult<T>) -> WithStatus<Json> {
+    match result {
+        Ok(value) => warp::reply::with_status(warp::reply::json(&value), StatusCode::OK),
+        Err(error) => ApiError::from(error).reply(),
+    }
+}
+
+/// Rejects `address` with 400 unless it's a valid base58 encoded public key.
+fn check_address(address: &str) -> Result<(), WithStatus<Json>> {
+    parse_address(address)
+        .map(|_| ())
+        .map_err(|error| bad_request(&format!("Invalid address '{}': {}", address, error)))
+}
+
+/// Cursors are served hex encoded, so clients don't rely on their content.
+fn encode_cursor<Cursor: ToString>(cursor: Cursor) -> String {
+    cursor.to_string().bytes().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_cursor<Cursor: FromStr>(cursor: &str) -> Option<Cursor> {
+    if !cursor.len().is_multiple_of(2) || !cursor.is_ascii() {
+        return None;
+    }
+    let bytes = (0..cursor.len()).step_by(2)
+        .map(|index| u8::from_str_radix(&cursor[index..index + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()?.parse().ok()
+}
+
+fn parse_page<Cursor: FromStr>(
+    limit: Option<usize>,
+    cursor: Option<String>,
+    order: Option<Order>,
+) -> Result<Page<Cursor>, WithStatus<Json>> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    if limit == 0 || limit > MAX_PAGE_LIMIT {
+        return Err(bad_request(&format!("The query parameter limit must be between 1 and {}", MAX_PAGE_LIMIT)));
+    }
+    let cursor = match cursor {
+        Some(cursor) => {
+            Some(decode_cursor(&cursor).ok_or_else(|| bad_request(&format!("Invalid cursor '{}'", cursor)))?)
+        }
+        None => None,
+    };
+    Ok(Page {limit, cursor, order: order.unwrap_or_default()})
+}
+
+/// Replies with the summary in `result`, or 404 if nothing about it was observed.
+fn summary_reply<Summary: Serialize>(result: StorageResult<Option<Summary>>, name: &str) -> WithStatus<Json> {
+    match result {
+        Ok(Some(summary)) => warp::reply::with_status(warp::reply::json(&summary), StatusCode::OK),
+        Ok(None) => ApiError::new(ErrorCode::NotFound, format!("Unknown {}", name)).reply(),
+        Err(error) => ApiError::from(error).reply(),
+    }
+}
+
+/// Removes the transactions which don't match the `status` filter.
+fn filter_status<Data>(transactions: &mut Vec<TransactionWithMeta<Data>>, status: Option<StatusFilter>) {
+    if let Some(status) = status {
+        transactions.retain(|transaction| status.matches(transaction.meta.as_ref()));
+    }
+}
+
+/// Replies with the transactions which match the `status` filter.
+fn transactions_reply<Data: Serialize>(
+    result: StorageResult<Vec<TransactionWithMeta<Data>>>,
+    status: Option<StatusFilter>,
+) -> WithStatus<Json> {
+    json_reply(result.map(|mut transactions| {
+        filter_status(&mut transactions, status);
+        transactions
+    }))
+}
+
+async fn get_transactions(
+    params: TransactionQuery,
+    storage_interface: StorageInterface,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let paged = params.limit.is_some() || params.cursor.is_some() || params.order.is_some();
+    if paged && params.address.is_none() {
+        return Ok(bad_request("The query parameters limit, cursor and order are only supported together with address"))
+    }
+    let filter = params.filter();
+    if params.has_address_filter() && params.address.is_none() {
+        return Ok(bad_request(concat!(
+            "The query parameters from, to, min_amount, max_amount and direction are only supported together ",
+            "with address",
+        )))
+    }
+    if filter.from.zip(filter.to).is_some_and(|(from, to)| to < from) {
+        return Ok(bad_request("The query parameter to must not be below from"));
+    }
+    if filter.min_amount.zip(filter.max_amount).is_some_and(|(min_amount, max_amount)| max_amount < min_amount) {
+        return Ok(bad_request("The query parameter max_amount must not be below min_amount"));
+    }
+    if let Err(reply) = params.address.iter().chain(&params.mint).try_for_each(|address| check_address(address)) {
+        return Ok(reply);
+    }
+    let result = match (params.address, params.id, params.day, params.mint, params.memo) {
+        (Some(address), None, None, None, None) => {
+            let page = match parse_page(params.limit, params.cursor, params.order) {
+                Ok(page) => page,
+                Err(reply) => return Ok(reply),
+            };
+            // The status and kind are part of the filter, so the pages are filled with matching transactions.
+            let result = storage_interface.get_transactions(address, filter, page).await;
+            let result = result.map(|page| page.map_cursor(encode_cursor));
+            return Ok(json_reply(result.map(|page| PageReply {items: page.items, next_cursor: page.next_cursor})))
+        }
+        (None, Some(id), None, None, None) => storage_interface.get_transactions_by_signature(id).await,
+        (None, None, Some(day), None, None) => {
+            match NaiveDate::parse_from_str(&day, DAY_FORMAT) {
+                Ok(day) => storage_interface.get_transactions_by_day(day).await,
+                Err(error) => {
+                    return Ok(bad_request(
+                        &format!("Invalid day '{}', expected DD/MM/YYYY: {}", day, error)
+                    ))
+                }
+            }
+        }
+        (None, None, None, Some(_), None) if params.kind.is_some() => {
+            return Ok(bad_request("The query parameter kind is not supported together with mint"))
+        }
+        (None, None, None, Some(mint), None) => {
+            let result = storage_interface.get_token_transfers_by_mint(mint).await;
+            return Ok(transactions_reply(result, params.status))
+        }
+        (None, None, None, None, Some(memo)) => {
+            let filter = match params.memo_match.unwrap_or_default() {
+                MemoMatch::Exact => MemoFilter::Exact(memo),
+                MemoMatch::Prefix => MemoFilter::Prefix(memo),
+            };
+            storage_interface.get_transactions_by_memo(filter).await
+        }
+        _ => {
+            return Ok(bad_request("Exactly one of the query parameters is required: address, id, day, mint, memo"))
+        }
+    };
+    let result = result.map(|mut transactions| {
+        if let Some(kind) = params.kind {
+            transactions.retain(|transaction| transaction.data.kind == kind);
+        }
+        transactions
+    });
+    Ok(transactions_reply(result, params.status))
+}
+
+async fn get_program_instructions(
+    program_id: Address,
+    params: GetInstructions,
+    storage_interface: StorageInterface,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reply) = check_address(&program_id) {
+        return Ok(reply);
+    }
+    let result = storage_interface.get_instructions_by_program(program_id).await;
+    Ok(transactions_reply(result, params.status))
+}
+
+async fn get_program_custom_instructions(
+    program_id: Address,
+    params: GetInstructions,
+    storage_interface: StorageInterface,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reply) = check_address(&program_id) {
+        return Ok(reply);
+    }
+    let result = storage_interface.get_custom_instructions_by_program(program_id).await;
+    Ok(transactions_reply(result, params.status))
+}
+
+async fn get_account(
+    address: Address,
+    storage_interface: StorageInterface,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reply) = check_address(&address) {
+        return Ok(reply);
+    }
+    Ok(summary_reply(storage_interface.get_account(address).await, "account"))
+}
+
+async fn get_stake_account(
+    address: Address,
+    storage_interface: StorageInterface,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reply) = check_address(&address) {
+        return Ok(reply);
+    }
+    Ok(summary_reply(storage_interface.get_stake_account(address).await, "stake account"))
+}
+
+async fn get_validator(
+    vote_account: Address,
+    storage_interface: StorageInterface,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reply) = check_address(&vote_account) {
+        return Ok(reply);
+    }
+    Ok(summary_reply(storage_interface.get_validator(vote_account).await, "validator"))
+}
+
+async fn get_latest_block(
+    storage_interface: StorageInterface,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let result = storage_interface.get_last_block().await;
+    Ok(summary_reply(result.map(|block| block.map(BlockSummary::from)), "block"))
+}
+
+async fn get_block(
+    height: u64,
+    storage_interface: StorageInterface,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let result = storage_interface.get_blocks(height, height).await;
+    Ok(summary_reply(result.map(|blocks| blocks.into_iter().next().map(BlockSummary::from)), "block"))
+}
+
+async fn get_blocks(
+    params: GetBlocks,
+    storage_interface: StorageInterface,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if params.to < params.from {
+        return Ok(bad_request("The query parameter to must not be below from"));
+    }
+    if params.to - params.from >= MAX_BLOCK_RANGE {
+        return Ok(bad_request(&format!("At most {} blocks can be queried at once", MAX_BLOCK_RANGE)));
+    }
+    let result = storage_interface.get_blocks(params.from, params.to).await;
+    Ok(json_reply(result.map(|blocks| blocks.into_iter().map(BlockSummary::from).collect::<Vec<_>>())))
+}
+
+async fn get_rewards(
+    params: GetRewards,
+    storage_interface: StorageInterface,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reply) = check_address(&params.address) {
+        return Ok(reply);
+    }
+    Ok(json_reply(storage_interface.get_rewards(params.address).await))
+}
+
+async fn get_block_rewards(
+    height: u64,
+    storage_interface: StorageInterface,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(summary_reply(storage_interface.get_block_rewards(height).await, "block"))
+}
+
+async fn get_token_balances(
+    owner: Address,
+    storage_interface: StorageInterface,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(reply) = check_address(&owner) {
+        return Ok(reply);
+    }
+    Ok(json_reply(storage_interface.get_token_balances(owner).await))
+}
+
+async fn get_accounts(
+    params: GetAccounts,
+    storage_interface: StorageInterface,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let page = match parse_page::<Address>(params.limit, params.cursor, params.order) {
+        Ok(page) => page,
+        Err(reply) => return Ok(reply),
+    };
+    if let Err(reply) = page.cursor.as_deref().map_or(Ok(()), check_address) {
+        return Ok(reply);
+    }
+    let result = storage_interface.get_accounts(page).await;
+    Ok(json_reply(result.map(|accounts| {
+        PageReply {items: accounts.items, next_cursor: accounts.next_cursor.map(encode_cursor)}
+    })))
+}
+
+/// Replies to the requests rejected by the routes with an error in the same format as the handlers.
+async fn handle_rejection(rejection: warp::Rejection) -> Result<WithStatus<Json>, Infallible> {
+    let error = if rejection.is_not_found() {
+        ApiError::new(ErrorCode::NotFound, "Unknown route")
+    } else if let Some(error) = rejection.find::<InvalidQuery>() {
+        ApiError::new(ErrorCode::InvalidRequest, error.to_string())
+    } else if let Some(error) = rejection.find::<MissingHeader>() {
+        ApiError::new(ErrorCode::InvalidRequest, error.to_string())
+    } else if let Some(error) = rejection.find::<InvalidHeader>() {
+        ApiError::new(ErrorCode::InvalidRequest, error.to_string())
+    } else if let Some(error) = rejection.find::<MissingConnectionUpgrade>() {
+        ApiError::new(ErrorCode::InvalidRequest, error.to_string())
+    } else if let Some(error) = rejection.find::<MethodNotAllowed>() {
+        ApiError::new(ErrorCode::MethodNotAllowed, error.to_string())
+    } else {
+        log::error!("Unhandled rejection: {:?}", rejection);
+        ApiError::new(ErrorCode::Internal, "Unhandled rejection")
+    };
+    Ok(error.reply())
+}
+
+fn routes(
+    storage_interface: StorageInterface,
+    subscriptions: Subscriptions,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = Infallible> + Clone {
+    let get_transactions_interface = storage_interface.clone();
+    let get_transactions_route = warp::path!("transactions")
+        .and(warp::query::<TransactionQuery>())
+        .and(warp::any().map(move || get_transactions_interface.clone()))
+        .and_then(get_transactions);
+    let get_accounts_interface = storage_interface.clone();
+    let get_accounts_route = warp::path!("accounts")
+        .and(warp::query::<GetAccounts>())
+        .and(warp::any().map(move || get_accounts_interface.clone()))
+        .and_then(get_accounts);
+    let get_account_interface = storage_interface.clone();
+    let get_account_route = warp::path!("accounts" / Address)
+        .and(warp::any().map(move || get_account_interface.clone()))
+        .and_then(get_account);
+    let get_token_balances_interface = storage_interface.clone();
+    let get_token_balances_route = warp::path!("tokens" / Address)
+        .and(warp::any().map(move || get_token_balances_interface.clone()))
+        .and_then(get_token_balances);
+    let get_program_instructions_interface = storage_interface.clone();
+    let get_program_instructions_route = warp::path!("programs" / Address / "instructions")
+        .and(warp::query::<GetInstructions>())
+        .and(warp::any().map(move || get_program_instructions_interface.clone()))
+        .and_then(get_program_instructions);
+    let get_program_custom_instructions_interface = storage_interface.clone();
+    let get_program_custom_instructions_route = warp::path!("programs" / Address / "custom_instructions")
+        .and(warp::query::<GetInstructions>())
+        .and(warp::any().map(move || get_program_custom_instructions_interface.clone()))
+        .and_then(get_program_custom_instructions);
+    let get_stake_account_interface = storage_interface.clone();
+    let get_stake_account_route = warp::path!("stake" / Address)
+        .and(warp::any().map(move || get_stake_account_interface.clone()))
+        .and_then(get_stake_account);
+    let get_validator_interface = storage_interface.clone();
+    let get_validator_route = warp::path!("validators" / Address)
+        .and(warp::any().map(move || get_validator_interface.clone()))
+        .and_then(get_validator);
+    let get_rewards_interface = storage_interface.clone();
+    let get_rewards_route = warp::path!("rewards")
+        .and(warp::query::<GetRewards>())
+        .and(warp::any().map(move || get_rewards_interface.clone()))
+        .and_then(get_rewards);
+    let get_block_rewards_interface = storage_interface.clone();
+    let get_block_rewards_route = warp::path!("blocks" / u64 / "rewards")
+        .and(warp::any().map(move || get_block_rewards_interface.clone()))
+        .and_then(get_block_rewards);
+    let get_latest_block_interface = storage_interface.clone();
+    let get_latest_block_route = warp::path!("blocks" / "latest")
+        .and(warp::any().map(move || get_latest_block_interface.clone()))
+        .and_then(get_latest_block);
+    let get_block_interface = storage_interface.clone();
+    let get_block_route = warp::path!("blocks" / u64)
+        .and(warp::any().map(move || get_block_interface.clone()))
+        .and_then(get_block);
+    let get_blocks_interface = storage_interface.clone();
+    let get_blocks_route = warp::path!("blocks")
+        .and(warp::query::<GetBlocks>())
+        .and(warp::any().map(move || get_blocks_interface.clone()))
+        .and_then(get_blocks);
+    let subscribe_websocket_subscriptions = subscriptions.clone();
+    let subscribe_websocket_route = warp::path!("subscribe")
+        .and(warp::ws())
+        .and(warp::query::<SubscribeQuery>())
+        .and(warp::any().map(move || subscribe_websocket_subscriptions.clone()))
+        .and_then(subscribe_websocket);
+    let subscribe_sse_route = warp::path!("subscribe" / "sse")
+        .and(warp::query::<SubscribeQuery>())
+        .and(warp::any().map(move || subscriptions.clone()))
+        .and_then(subscribe_sse);
+    let routes = get_accounts_route
+        .or(get_account_route)
+        .or(get_transactions_route)
+        .or(get_token_balances_route)
+        .or(get_program_instructions_route)
+        .or(get_program_custom_instructions_route)
+        .or(get_stake_account_route)
+        .or(get_validator_route)
+        .or(get_rewards_route)
+        .or(get_block_rewards_route)
+        .or(get_latest_block_route)
+        .or(get_block_route)
+        .or(get_blocks_route)
+        .or(subscribe_websocket_route)
+        .or(subscribe_sse_route);
+    warp::get().and(routes).recover(handle_rejection)
+}
+
+pub async fn run_api(
+    address: SocketAddr,
+    storage_interface: StorageInterface,
+    subscriptions: Subscriptions,
+    token: CancellationToken,
+) {
+    warp::serve(routes(storage_interface, subscriptions)).bind_with_graceful_shutdown(address, async move {
+        token.cancelled().await;
+        log::debug!("bind_with_graceful_shutdown() interrupted");
+    }).1.await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::Memory;
+    use crate::storage::{AccountingMode, Storage};
+    use crate::types::{BalanceChange, Block, Transaction};
+    use solana_sdk::pubkey::Pubkey;
+    use tokio::sync::{broadcast, mpsc};
+
+    fn address(byte: u8) -> Address {
+        Pubkey::new_from_array([byte; 32]).to_string()
+    }
+
+    fn block(height: u64) -> Block {
+        Block {
+            slot: height * 2,
+            parent_slot: height.saturating_sub(1) * 2,
+            height,
+            hash: height.to_string(),
+            previous_blockhash: height.saturating_sub(1).to_string(),
+            timestamp: height as i64,
+            transactions: Vec::from([Transaction {
+                signature: "a".to_string(),
+                instruction_index: 0,
+                invocation: None,
+                kind: SystemInstructionKind::Transfer,
+                sender: address(0),
+                receiver: address(1),
+                amount: 1,
+            }]),
+            balance_changes: Vec::from([BalanceChange {address: address(0), pre_balance: 10, post_balance: 9}]),
+            transaction_metas: Vec::new(),
+            token_transfers: Vec::new(),
+            token_balance_changes: Vec::new(),
+            raw_instructions: Vec::new(),
+            custom_instructions: Vec::new(),
+            stake_instructions: Vec::new(),
+            votes: Vec::new(),
+            rewards: Vec::new(),
+        }
+    }
+
+    async fn start_storage(token: &CancellationToken) -> StorageInterface {
+        let (storage_tx, storage_rx) = mpsc::channel(20);
+        let storage_token = token.clone();
+        tokio::spawn(async move {
+            Memory::new(AccountingMode::Transfers).run(storage_rx, storage_token).await
+        });
+        StorageInterface::new(storage_tx)
+    }
+
+    async fn request(
+        storage_interface: &StorageInterface,
+        method: &str,
+        path: &str,
+    ) -> (StatusCode, Option<ErrorCode>) {
+        // The event streams of the subscriptions end right away after shutdown.
+        let shutdown = CancellationToken::new();
+        shutdown.cancel();
+        let subscriptions = Subscriptions::new(broadcast::channel(1).0, shutdown);
+        let response = warp::test::request()
+            .method(method)
+            .path(path)
+            .reply(&routes(storage_interface.clone(), subscriptions))
+            .await;
+        let code = serde_json::from_slice::<ErrorReply>(response.body()).ok().map(|reply| reply.error.code);
+        (response.status(), code)
+    }
+
+    async fn assert_status(storage_interface: &StorageInterface, path: &str, expected: StatusCode) {
+        let (status, code) = request(storage_interface, "GET", path).await;
+        assert_eq!(status, expected, "{}", path);
+        assert_eq!(code.map(|code| code.status()), (!expected.is_success()).then_some(expected), "{}", path);
+    }
+
+    #[tokio::test]
+    async fn test_status_codes() {
+        let token = CancellationToken::new();
+        let storage_interface = start_storage(&token).await;
+        assert_status(&storage_interface, "/blocks/latest", StatusCode::NOT_FOUND).await;
+        storage_interface.add_block(block(1)).await.unwrap();
+
+        let (known, unknown) = (address(0), address(2));
+        let cases = [
+            ("/accounts".to_string(), StatusCode::OK),
+            ("/accounts?limit=1&order=desc".to_string(), StatusCode::OK),
+            ("/accounts?limit=0".to_string(), StatusCode::BAD_REQUEST),
+            ("/accounts?limit=abc".to_string(), StatusCode::BAD_REQUEST),
+            ("/accounts?cursor=zz".to_string(), StatusCode::BAD_REQUEST),
+            (format!("/accounts/{known}"), StatusCode::OK),
+            (format!("/accounts/{unknown}"), StatusCode::NOT_FOUND),
+            (format!("/transactions?address={known}"), StatusCode::OK),
+            (format!("/transactions?address={known}&status=success&kind=transfer"), StatusCode::OK),
+            (format!("/transactions?address={known}&status=unknown"), StatusCode::BAD_REQUEST),
+            (format!("/transactions?address={known}&from=0&to=10&min_amount=1&max_amount=5"), StatusCode::OK),
+            (format!("/transactions?address={known}&direction=self&order=desc"), StatusCode::OK),
+            (format!("/transactions?address={known}&direction=sideways"), StatusCode::BAD_REQUEST),
+            (format!("/transactions?address={known}&from=2&to=1"), StatusCode::BAD_REQUEST),
+            (format!("/transactions?address={known}&min_amount=5&max_amount=1"), StatusCode::BAD_REQUEST),
+            (format!("/transactions?address={known}&min_amount=-1"), StatusCode::BAD_REQUEST),
+            ("/transactions?id=a&direction=in".to_string(), StatusCode::BAD_REQUEST),
+            ("/transactions?id=a".to_string(), StatusCode::OK),
+            ("/transactions?id=a&limit=1".to_string(), StatusCode::BAD_REQUEST),
+            ("/transactions?day=01/01/1970".to_string(), StatusCode::OK),
+            ("/transactions?day=1970-01-01".to_string(), StatusCode::BAD_REQUEST),
+            (format!("/transactions?mint={known}"), StatusCode::OK),
+            (format!("/transactions?mint={known}&kind=transfer"), StatusCode::BAD_REQUEST),
+            ("/transactions?memo=a&memo_match=prefix".to_string(), StatusCode::OK),
+            ("/transactions".to_string(), StatusCode::BAD_REQUEST),
+            (format!("/transactions?address={known}&id=a"), StatusCode::BAD_REQUEST),
+            (format!("/tokens/{known}"), StatusCode::OK),
+            (format!("/programs/{known}/instructions"), StatusCode::OK),
+            (format!("/programs/{known}/instructions?status=unknown"), StatusCode::BAD_REQUEST),
+            (format!("/programs/{known}/custom_instructions?status=success"), StatusCode::OK),
+            ("/programs/0/custom_instructions".to_string(), StatusCode::BAD_REQUEST),
+            (format!("/stake/{known}"), StatusCode::NOT_FOUND),
+            (format!("/validators/{known}"), StatusCode::NOT_FOUND),
+            (format!("/rewards?address={known}"), StatusCode::OK),
+            ("/rewards".to_string(), StatusCode::BAD_REQUEST),
+            ("/blocks/1/rewards".to_string(), StatusCode::OK),
+            ("/blocks/2/rewards".to_string(), StatusCode::NOT_FOUND),
+            ("/blocks/latest".to_string(), StatusCode::OK),
+            ("/blocks/1".to_string(), StatusCode::OK),
+            ("/blocks/2".to_string(), StatusCode::NOT_FOUND),
+            ("/blocks/abc".to_string(), StatusCode::NOT_FOUND),
+            ("/blocks?from=1&to=1".to_string(), StatusCode::OK),
+            ("/blocks?from=2&to=1".to_string(), StatusCode::BAD_REQUEST),
+            ("/blocks?from=0&to=100".to_string(), StatusCode::BAD_REQUEST),
+            ("/blocks?from=0".to_string(), StatusCode::BAD_REQUEST),
+            ("/unknown".to_string(), StatusCode::NOT_FOUND),
+            (format!("/subscribe/sse?addresses={known},{unknown}"), StatusCode::OK),
+            ("/subscribe".to_string(), StatusCode::BAD_REQUEST),
+            ("/subscribe/sse?addresses=0".to_string(), StatusCode::BAD_REQUEST),
+            ("/accounts/0".to_string(), StatusCode::BAD_REQUEST),
+            (format!("/accounts?cursor={}", encode_cursor("0")), StatusCode::BAD_REQUEST),
+            ("/transactions?address=0OIl".to_string(), StatusCode::BAD_REQUEST),
+            ("/transactions?mint=0".to_string(), StatusCode::BAD_REQUEST),
+            ("/tokens/0".to_string(), StatusCode::BAD_REQUEST),
+            ("/programs/0/instructions".to_string(), StatusCode::BAD_REQUEST),
+            ("/stake/0".to_string(), StatusCode::BAD_REQUEST),
+            ("/validators/0".to_string(), StatusCode::BAD_REQUEST),
+            ("/rewards?address=0".to_string(), StatusCode::BAD_REQUEST),
+        ];
+        for (path, expected) in cases {
+            assert_status(&storage_interface, &path, expected).await;
+        }
+        assert_eq!(
+            request(&storage_interface, "POST", "/accounts").await,
+            (StatusCode::METHOD_NOT_ALLOWED, Some(ErrorCode::MethodNotAllowed))
+        );
+
+        // Without a running storage all storage queries are unavailable.
+        token.cancel();
+        let (storage_tx, _) = mpsc::channel(1);
+        let storage_interface = StorageInterface::new(storage_tx);
+        let paths = [
+            "/accounts".to_string(),
+            format!("/accounts/{}", known),
+            format!("/transactions?address={}", known),
+            "/blocks/latest".to_string(),
+            format!("/rewards?address={}", known),
+        ];
+        for path in paths {
+            assert_status(&storage_interface, &path, StatusCode::SERVICE_UNAVAILABLE).await;
+        }
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let cases = [
+            (Error::ChannelFailure("storage".to_string(), "send failure".to_string()), ErrorCode::Unavailable),
+            (Error::Shutdown, ErrorCode::Unavailable),
+            (Error::SlotNotAvailable(1), ErrorCode::Unavailable),
+            (Error::SlotSkippedOrMissing(1), ErrorCode::NotFound),
+            (Error::UnknownParser("parser".to_string()), ErrorCode::InvalidRequest),
+            (Error::InvalidBlock(1, "reason".to_string()), ErrorCode::Internal),
+            (Error::SqliteError(rusqlite::Error::InvalidQuery), ErrorCode::Internal),
+        ];
+        for (error, code) in cases {
+            let message = error.to_string();
+            let error = ApiError::from(error);
+            assert_eq!((error.code, error.message), (code, message));
+        }
+    }
+}
@@ -0,0 +1,394 @@
+use crate::aggregator::BlockSender;
+use crate::api::bad_request;
+use crate::types::{parse_address, Address, BlockSummary, Hash, TransactionWithMeta};
+use futures::{stream, SinkExt, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use solana_sdk::clock::Slot;
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_util::sync::CancellationToken;
+use warp::reply::{Json, Response, WithStatus};
+use warp::sse::Event;
+use warp::ws::{Message, WebSocket, Ws};
+use warp::Reply;
+
+/// The maximum number of addresses a single client can subscribe to.
+const MAX_SUBSCRIBED_ADDRESSES: usize = 100;
+
+/// Hands out the stored blocks to the subscribers until shutdown.
+#[derive(Clone)]
+pub struct Subscriptions {
+    blocks: BlockSender,
+    token: CancellationToken,
+}
+
+impl Subscriptions {
+    pub fn new(blocks: BlockSender, token: CancellationToken) -> Self {
+        Self {blocks, token}
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SubscribeQuery {
+    /// Comma separated addresses.
+    pub addresses: Option<String>,
+}
+
+/// The messages clients send over the WebSocket to change the subscribed addresses.
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum ClientMessage {
+    Subscribe {addresses: Vec<Address>},
+    Unsubscribe {addresses: Vec<Address>},
+}
+
+/// A block without its transfers.
+#[derive(Serialize)]
+struct BlockHeader<'a> {
+    height: u64,
+    slot: Slot,
+    parent_slot: Slot,
+    hash: &'a Hash,
+    previous_blockhash: &'a Hash,
+    timestamp: i64,
+    transfer_count: usize,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum ServerEvent<'a> {
+    Block(BlockHeader<'a>),
+    /// A transfer from or to one of the subscribed addresses.
+    Transfer(&'a TransactionWithMeta),
+    /// The client didn't keep up and missed the events of `skipped` blocks.
+    Lagged {skipped: u64},
+    /// The last client message was rejected.
+    Error {message: String},
+}
+
+impl ServerEvent<'_> {
+    fn name(&self) -> &'static str {
+        match self {
+            ServerEvent::Block(_) => "block",
+            ServerEvent::Transfer(_) => "transfer",
+            ServerEvent::Lagged {..} => "lagged",
+            ServerEvent::Error {..} => "error",
+        }
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// The addresses a client subscribed to, it receives the headers of all blocks regardless.
+#[derive(Debug, Default, PartialEq)]
+struct Subscription {
+    addresses: HashSet<Address>,
+}
+
+impl Subscription {
+    fn subscribe(&mut self, addresses: Vec<Address>) -> Result<(), String> {
+        if let Some((address, error)) = addresses.iter()
+            .find_map(|address| parse_address(address).err().map(|error| (address, error))) {
+            return Err(format!("Invalid address '{}': {}", address, error));
+        }
+        let new_addresses = addresses.iter().filter(|address| !self.addresses.contains(*address)).count();
+        if self.addresses.len() + new_addresses > MAX_SUBSCRIBED_ADDRESSES {
+            return Err(format!("At most {} addresses can be subscribed to", MAX_SUBSCRIBED_ADDRESSES));
+        }
+        self.addresses.extend(addresses);
+        Ok(())
+    }
+
+    /// Applies the JSON encoded client `message`.
+    fn update(&mut self, message: &str) -> Result<(), String> {
+        let message = serde_json::from_str(message).map_err(|error| format!("Invalid message: {}", error))?;
+        match message {
+            ClientMessage::Subscribe {addresses} => self.subscribe(addresses),
+            ClientMessage::Unsubscribe {addresses} => {
+                for address in &addresses {
+                    self.addresses.remove(address);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// The header of `block` followed by its transfers involving the subscribed addresses.
+    fn events<'a>(&self, block: &'a BlockSummary) -> Vec<ServerEvent<'a>> {
+        let header = BlockHeader {
+            height: block.height,
+            slot: block.slot,
+            parent_slot: block.parent_slot,
+            hash: &block.hash,
+            previous_blockhash: &block.previous_blockhash,
+            timestamp: block.timestamp,
+            transfer_count: block.transfer_count,
+        };
+        let transfers = block.transfers.iter()
+            .filter(|transfer| {
+                self.addresses.contains(&transfer.data.sender) || self.addresses.contains(&transfer.data.receiver)
+            })
+            .map(ServerEvent::Transfer);
+        std::iter::once(ServerEvent::Block(header)).chain(transfers).collect()
+    }
+}
+
+/// Waits for the next block and serializes its events for the subscription, or `None` after shutdown.
+///
+/// The blocks are buffered in the broadcast channel, a client which falls behind by more than its
+/// capacity misses the oldest blocks and gets told about it instead of slowing down the aggregator.
+async fn next_events<Item>(
+    subscription: &Subscription,
+    blocks: &mut broadcast::Receiver<Arc<BlockSummary>>,
+    token: &CancellationToken,
+    item: impl Fn(&ServerEvent) -> Item,
+) -> Option<Vec<Item>> {
+    let block = tokio::select! {
+        _ = token.cancelled() => return None,
+        block = blocks.recv() => block,
+    };
+    match block {
+        Ok(block) => Some(subscription.events(&block).iter().map(item).collect()),
+        Err(RecvError::Lagged(skipped)) => Some(Vec::from([item(&ServerEvent::Lagged {skipped})])),
+        Err(RecvError::Closed) => None,
+    }
+}
+
+fn parse_subscription(query: SubscribeQuery) -> Result<Subscription, WithStatus<Json>> {
+    let mut subscription = Subscription::default();
+    let addresses = query.addresses.iter()
+        .flat_map(|addresses| addresses.split(','))
+        .filter(|address| !address.is_empty())
+        .map(str::to_string)
+        .collect();
+    subscription.subscribe(addresses).map_err(|error| bad_request(&error))?;
+    Ok(subscription)
+}
+
+async fn run_websocket(
+    socket: WebSocket,
+    mut subscription: Subscription,
+    mut blocks: broadcast::Receiver<Arc<BlockSummary>>,
+    token: CancellationToken,
+) {
+    let (mut sender, mut receiver) = socket.split();
+    loop {
+        let messages = tokio::select! {
+            message = receiver.next() => {
+                let message = match message {
+                    Some(Ok(message)) => message,
+                    Some(Err(error)) => {
+                        log::debug!("WebSocket failure: {}", error);
+                        return
+                    }
+                    None => return,
+                };
+                if message.is_close() {
+                    return
+                }
+                let Ok(text) = message.to_str() else {
+                    continue
+                };
+                match subscription.update(text) {
+                    Ok(()) => continue,
+                    Err(message) => Vec::from([Message::text(ServerEvent::Error {message}.to_json())]),
+                }
+            }
+            messages = next_events(&subscription, &mut blocks, &token, |event| Message::text(event.to_json())) => {
+                match messages {
+                    Some(messages) => messages,
+                    None => {
+                        let _ = sender.send(Message::close()).await;
+                        return
+                    }
+                }
+            }
+        };
+        // Sending waits for slow clients, while the broadcast channel keeps buffering their blocks.
+        for message in messages {
+            if sender.send(message).await.is_err() {
+                return
+            }
+        }
+    }
+}
+
+fn sse_events(
+    subscription: Subscription,
+    blocks: broadcast::Receiver<Arc<BlockSummary>>,
+    token: CancellationToken,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    stream::unfold((subscription, blocks, token), |(subscription, mut blocks, token)| async move {
+        let events = next_events(&subscription, &mut blocks, &token, |event| {
+            Event::default().event(event.name()).data(event.to_json())
+        }).await?;
+        Some((stream::iter(events.into_iter().map(Ok)), (subscription, blocks, token)))
+    }).flatten()
+}
+
+pub async fn subscribe_websocket(
+    ws: Ws,
+    query: SubscribeQuery,
+    subscriptions: Subscriptions,
+) -> Result<Response, warp::Rejection> {
+    let subscription = match parse_subscription(query) {
+        Ok(subscription) => subscription,
+        Err(reply) => return Ok(reply.into_response()),
+    };
+    let blocks = subscriptions.blocks.subscribe();
+    let reply = ws.on_upgrade(move |socket| run_websocket(socket, subscription, blocks, subscriptions.token));
+    Ok(reply.into_response())
+}
+
+pub async fn subscribe_sse(
+    query: SubscribeQuery,
+    subscriptions: Subscriptions,
+) -> Result<Response, warp::Rejection> {
+    let subscription = match parse_subscription(query) {
+        Ok(subscription) => subscription,
+        Err(reply) => return Ok(reply.into_response()),
+    };
+    let events = sse_events(subscription, subscriptions.blocks.subscribe(), subscriptions.token);
+    Ok(warp::sse::reply(warp::sse::keep_alive().stream(events)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::routes;
+    use crate::storage::StorageInterface;
+    use crate::types::{SystemInstructionKind, Transaction};
+    use serde_json::{json, Value};
+    use solana_sdk::pubkey::Pubkey;
+    use tokio::sync::mpsc;
+    use warp::test::WsClient;
+
+    fn address(byte: u8) -> Address {
+        Pubkey::new_from_array([byte; 32]).to_string()
+    }
+
+    fn block(height: u64, transfers: &[(u8, u8)]) -> Arc<BlockSummary> {
+        let transfers: Vec<TransactionWithMeta> = transfers.iter()
+            .map(|(sender, receiver)| TransactionWithMeta {
+                data: Transaction {
+                    signature: format!("{}{}", sender, receiver),
+                    instruction_index: 0,
+                    invocation: None,
+                    kind: SystemInstructionKind::Transfer,
+                    sender: address(*sender),
+                    receiver: address(*receiver),
+                    amount: 1,
+                },
+                timestamp: height as i64,
+                meta: None,
+            })
+            .collect();
+        Arc::new(BlockSummary {
+            height,
+            slot: height,
+            parent_slot: height - 1,
+            hash: height.to_string(),
+            previous_blockhash: (height - 1).to_string(),
+            timestamp: height as i64,
+            transfer_count: transfers.len(),
+            transfers,
+        })
+    }
+
+    async fn next_names(
+        subscription: &Subscription,
+        blocks: &mut broadcast::Receiver<Arc<BlockSummary>>,
+        token: &CancellationToken,
+    ) -> Option<Vec<&'static str>> {
+        next_events(subscription, blocks, token, |event| event.name()).await
+    }
+
+    async fn recv(client: &mut WsClient) -> Value {
+        serde_json::from_str(client.recv().await.unwrap().to_str().unwrap()).unwrap()
+    }
+
+    /// The event names and the signatures of the transfers.
+    fn summarize(events: &[ServerEvent]) -> Vec<String> {
+        events.iter()
+            .map(|event| match event {
+                ServerEvent::Transfer(transfer) => transfer.data.signature.clone(),
+                event => event.name().to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_subscription() {
+        let mut subscription = Subscription::default();
+        let block = block(1, &[(1, 2), (2, 3), (3, 3)]);
+        assert_eq!(summarize(&subscription.events(&block)), ["block"]);
+
+        let subscribe = json!({"action": "subscribe", "addresses": [address(2), address(3)]}).to_string();
+        assert_eq!(subscription.update(&subscribe), Ok(()));
+        assert_eq!(summarize(&subscription.events(&block)), ["block", "12", "23", "33"]);
+        let unsubscribe = json!({"action": "unsubscribe", "addresses": [address(2)]}).to_string();
+        assert_eq!(subscription.update(&unsubscribe), Ok(()));
+        assert_eq!(summarize(&subscription.events(&block)), ["block", "23", "33"]);
+
+        // Rejected messages don't change the subscription.
+        let invalid = json!({"action": "subscribe", "addresses": [address(1), "0OIl"]}).to_string();
+        assert_eq!(subscription.update(&invalid), Err("Invalid address '0OIl': Invalid Base58 string".to_string()));
+        assert!(subscription.update(&json!({"action": "unknown"}).to_string()).is_err());
+        assert!(subscription.update("{").is_err());
+        let too_many: Vec<Address> = (0..=MAX_SUBSCRIBED_ADDRESSES as u8).map(address).collect();
+        assert!(subscription.update(&json!({"action": "subscribe", "addresses": too_many}).to_string()).is_err());
+        assert_eq!(subscription.addresses, HashSet::from([address(3)]));
+
+        let event = ServerEvent::Lagged {skipped: 2};
+        assert_eq!(serde_json::from_str::<Value>(&event.to_json()).unwrap(), json!({"event": "lagged", "skipped": 2}));
+    }
+
+    #[tokio::test]
+    async fn test_next_events() {
+        let token = CancellationToken::new();
+        let (blocks_tx, mut blocks) = broadcast::channel(2);
+        let subscription = Subscription::default();
+        for height in 1..=4 {
+            blocks_tx.send(block(height, &[])).unwrap();
+        }
+        // The oldest blocks are dropped for subscribers which fall behind.
+        assert_eq!(next_names(&subscription, &mut blocks, &token).await, Some(Vec::from(["lagged"])));
+        assert_eq!(next_names(&subscription, &mut blocks, &token).await, Some(Vec::from(["block"])));
+        assert_eq!(next_names(&subscription, &mut blocks, &token).await, Some(Vec::from(["block"])));
+        token.cancel();
+        assert_eq!(next_names(&subscription, &mut blocks, &token).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_websocket() {
+        let token = CancellationToken::new();
+        let (blocks_tx, _) = broadcast::channel(10);
+        let (storage_tx, _) = mpsc::channel(1);
+        let routes = routes(StorageInterface::new(storage_tx), Subscriptions::new(blocks_tx.clone(), token.clone()));
+        let mut client = warp::test::ws()
+            .path(&format!("/subscribe?addresses={}", address(1)))
+            .handshake(routes)
+            .await
+            .unwrap();
+
+        blocks_tx.send(block(1, &[(1, 2), (2, 3)])).unwrap();
+        assert_eq!(recv(&mut client).await["event"], "block");
+        assert_eq!(recv(&mut client).await["data"]["signature"], "12");
+
+        // The error reply confirms that the subscribe message before it was applied.
+        client.send_text(json!({"action": "subscribe", "addresses": [address(3)]}).to_string()).await;
+        client.send_text("invalid").await;
+        assert_eq!(recv(&mut client).await["event"], "error");
+        blocks_tx.send(block(2, &[(2, 3)])).unwrap();
+        assert_eq!(recv(&mut client).await["height"], 2);
+        assert_eq!(recv(&mut client).await["data"]["signature"], "23");
+
+        token.cancel();
+        assert!(client.recv_closed().await.is_ok());
+    }
+}
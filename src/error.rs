@@ -5,17 +5,22 @@ use thiserror::Error;
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug, Error)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     #[error("Channel failure for: {0} - Failure: {1}")]
     ChannelFailure(String, String),
     #[error("RPC error: {0}")]
-    RpcError(ClientError),
+    RpcError(Box<ClientError>),
     #[error("Slot {0} not available")]
     SlotNotAvailable(Slot),
     #[error("Slot {0} was skipped or is missing")]
     SlotSkippedOrMissing(Slot),
     #[error("Invalid block with height {0} - Reason: {1}")]
     InvalidBlock(u64, String),
+    #[error("SQLite error: {0}")]
+    SqliteError(#[from] rusqlite::Error),
+    #[error("Unknown instruction parser: {0}")]
+    UnknownParser(String),
     #[error("Application shutdown triggered")]
     Shutdown,
 }
@@ -1,3 +1,6 @@
+pub mod aggregator;
+pub mod api;
 pub mod error;
 pub mod source;
-pub mod types;
\ No newline at end of file
+pub mod storage;
+pub mod types;
@@ -1,23 +1,20 @@
-mod error;
-mod api;
-mod aggregator;
-mod source;
-mod storage;
-mod types;
-
-use crate::aggregator::Aggregator;
-use crate::api::run_api;
-use crate::source::live::LiveStream;
-use crate::storage::memory::Memory;
-use crate::storage::{Storage, StorageInterface};
+use solana_aggregator::aggregator::Aggregator;
+use solana_aggregator::api::run_api;
+use solana_aggregator::api::subscribe::Subscriptions;
+use solana_aggregator::source::live::LiveStream;
+use solana_aggregator::source::parser::{parsers, set_parser_enabled};
+use solana_aggregator::storage::memory::Memory;
+use solana_aggregator::storage::sqlite::Sqlite;
+use solana_aggregator::storage::{AccountingMode, Storage, StorageInterface, StorageType};
 use log::LevelFilter;
 use simple_logger::SimpleLogger;
 use std::net::SocketAddr;
 use std::process::exit;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 use tokio_util::sync::CancellationToken;
 use clap::Parser;
-use crate::source::benchmark::Benchmark;
+use solana_aggregator::source::benchmark::Benchmark;
+use solana_sdk::commitment_config::CommitmentLevel;
 use tokio::signal;
 
 const DEFAULT_RPC_URL: &str = "https://api.devnet.solana.com";
@@ -35,6 +32,25 @@ struct Args {
     /// RPC interface call of the official Solana RPC interface.
     #[arg(short, long, default_value = None)]
     file_path: Option<String>,
+    /// The storage backend where the aggregated data is kept. Either `memory` or `sqlite:<path>`
+    /// with the path to the SQLite database file, which gets created if it doesn't exist yet.
+    #[arg(short, long, default_value = "memory")]
+    storage: StorageType,
+    /// The commitment level of the ingested blocks, one of `processed`, `confirmed` or `finalized`.
+    /// Blocks which are not finalized can still be replaced by a fork, which rolls back the
+    /// storage to the fork's parent.
+    #[arg(short, long, default_value = "finalized")]
+    commitment: CommitmentLevel,
+    /// How the account balances are computed. Either `transfers` to apply only the parsed transfers
+    /// or `balances` to take the balances after each block, including fees and rewards.
+    #[arg(short = 'm', long, default_value = "transfers")]
+    accounting: AccountingMode,
+    /// Enables the instruction parser with the given name, can be used multiple times.
+    #[arg(long = "enable-parser", value_name = "NAME")]
+    enable_parsers: Vec<String>,
+    /// Disables the instruction parser with the given name, can be used multiple times.
+    #[arg(long = "disable-parser", value_name = "NAME")]
+    disable_parsers: Vec<String>,
 }
 
 #[tokio::main]
@@ -49,14 +65,41 @@ async fn main() {
         .with_module_level("solana_aggregator", LevelFilter::Debug)
         .init()
         .expect("simple_logger init failed");
+    let parser_changes = args.enable_parsers.iter().map(|name| (name, true))
+        .chain(args.disable_parsers.iter().map(|name| (name, false)));
+    for (name, enabled) in parser_changes {
+        if let Err(error) = set_parser_enabled(name, enabled) {
+            log::error!("{}", error);
+            exit(1);
+        }
+    }
+    log::debug!("Instruction parsers: {:?}", parsers());
     let token = CancellationToken::new();
     let storage_token = token.clone();
     log::debug!("Create data storage");
     let (storage_tx, storage_rx) = mpsc::channel(20);
     let storage_interface = StorageInterface::new(storage_tx);
-    let storage_task = tokio::spawn(async move {
-        Memory::default().run(storage_rx, storage_token).await
-    });
+    let storage_task = match args.storage {
+        StorageType::Memory => {
+            tokio::spawn(async move {
+                Memory::new(args.accounting).run(storage_rx, storage_token).await
+            })
+        }
+        StorageType::Sqlite(path) => {
+            let mut sqlite = match Sqlite::open(&path, args.accounting) {
+                Ok(sqlite) => {sqlite}
+                Err(error) => {
+                    log::error!("Failed to open SQLite storage {:?}: {}", path, error);
+                    exit(1);
+                }
+            };
+            tokio::spawn(async move {
+                sqlite.run(storage_rx, storage_token).await
+            })
+        }
+    };
+    // Subscribers which fall behind by more blocks than buffered here miss the oldest ones.
+    let (blocks_tx, _) = broadcast::channel(64);
     log::debug!("Create source stream + aggregator and start it!");
     let aggregator_task;
     if let Some(file_path) = args.file_path {
@@ -64,7 +107,9 @@ async fn main() {
         let mut aggregator = Aggregator::new(
             stream,
             storage_interface.clone(),
-            token.clone()
+            blocks_tx.clone(),
+            token.clone(),
+            args.commitment
         );
         aggregator_task = tokio::spawn(async move {
             aggregator.run().await
@@ -75,8 +120,17 @@ async fn main() {
         } else {
             DEFAULT_RPC_URL.to_string()
         };
-        let stream = match LiveStream::create_with_latest_slot(
+        let last_slot = match storage_interface.get_last_slot().await {
+            Ok(last_slot) => {last_slot}
+            Err(error) => {
+                log::error!("Failed to fetch the last slot from storage {}", error);
+                exit(1);
+            }
+        };
+        let stream = match LiveStream::create(
             url,
+            last_slot,
+            args.commitment,
             token.clone()
         ).await {
             Ok(stream) => {stream}
@@ -88,7 +142,9 @@ async fn main() {
         let mut aggregator = Aggregator::new(
             stream,
             storage_interface.clone(),
-            token.clone()
+            blocks_tx.clone(),
+            token.clone(),
+            args.commitment
         );
         aggregator_task = tokio::spawn(async move {
             aggregator.run().await
@@ -99,6 +155,7 @@ async fn main() {
         run_api(
             args.api_socket,
             storage_interface.clone(),
+            Subscriptions::new(blocks_tx, token.clone()),
             token.clone()
         )
     );
@@ -112,7 +169,7 @@ async fn main() {
     });
 
     // Wait for all tasks to be done!
-    tokio::join!(
+    let _ = tokio::join!(
         storage_task,
         aggregator_task,
         api_task,
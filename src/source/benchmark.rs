@@ -37,14 +37,20 @@ impl SourceStream for Benchmark {
             }
             return SourceEvent::EndOfStream;
         }
-        let next_block = Block::from(self.blocks[self.current_block].clone());
+        // The RPC block data doesn't contain its own slot, so take it from the parent slot of the
+        // following block if there is one. Otherwise assume the slot directly after the parent.
+        let slot = match self.blocks.get(self.current_block + 1) {
+            Some(following) => following.parent_slot,
+            None => self.blocks[self.current_block].parent_slot + 1,
+        };
+        let next_block = Block::from((slot, self.blocks[self.current_block].clone()));
         log::debug!(
             "Block: {} Transactions: {}",
             next_block.height,
             next_block.transactions.len()
         );
         self.current_block += 1;
-        SourceEvent::Next(next_block)
+        SourceEvent::Next(Box::new(next_block))
     }
 }
 
@@ -63,7 +69,7 @@ mod tests {
                     assert_eq!(block.height, height);
                 }
                 SourceEvent::Failure(error) => {
-                    assert!(false, "unexpected failure {}", error)
+                    panic!("unexpected failure {}", error)
                 }
                 SourceEvent::EndOfStream => {
                     assert!(height >= 288381116)
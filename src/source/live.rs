@@ -6,6 +6,7 @@ use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_client::rpc_config::RpcBlockConfig;
 use solana_client::rpc_request::RpcError;
 use solana_sdk::clock::Slot;
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
 use solana_transaction_status::{UiConfirmedBlock, UiTransactionEncoding};
 use std::time::Duration;
 use tokio::time::sleep;
@@ -25,34 +26,63 @@ pub fn block_config() -> RpcBlockConfig {
 pub async fn block_for_slot(slot: Slot, rpc_client: &RpcClient, block_config: RpcBlockConfig) -> Result<UiConfirmedBlock> {
     rpc_client.get_block_with_config(slot, block_config).await
         .map_err(|error| {
-            if let ClientErrorKind::RpcError(rpc_error) = error.kind() {
-                if let RpcError::RpcResponseError { code, .. } = rpc_error {
-                    if code == &BLOCK_NOT_AVAILABLE {
-                        return Error::SlotNotAvailable(slot)
-                    }
-                    if code == &SLOT_SKIPPED {
-                        return Error::SlotSkippedOrMissing(slot)
-                    }
+            if let ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) = error.kind() {
+                if code == &BLOCK_NOT_AVAILABLE {
+                    return Error::SlotNotAvailable(slot)
+                }
+                if code == &SLOT_SKIPPED {
+                    return Error::SlotSkippedOrMissing(slot)
                 }
             }
-            Error::RpcError(error)
+            Error::RpcError(Box::new(error))
         })
 }
 
 pub struct LiveStream {
     rpc_client: RpcClient,
     current_slot: Slot,
+    backfill: Option<(Slot, Slot)>,
     block_config: RpcBlockConfig,
     token: CancellationToken,
 }
 
 impl LiveStream {
-    pub async fn create_with_latest_slot(url: String, token: CancellationToken) -> Result<Self> {
-        let rpc_client = RpcClient::new(url);
-        let current_slot = rpc_client.get_slot().await.map_err(|error| {
-            Error::RpcError(error)
+    /// Creates a stream starting directly after `last_slot` if given, which backfills all slots
+    /// up to the current tip before it continues with the live blocks. Without `last_slot` the
+    /// stream starts at the current tip. The tip and the blocks are fetched with the given
+    /// `commitment`, except for `processed` blocks which are not available via RPC so `confirmed`
+    /// is used for the blocks then.
+    pub async fn create(
+        url: String,
+        last_slot: Option<Slot>,
+        commitment: CommitmentLevel,
+        token: CancellationToken,
+    ) -> Result<Self> {
+        let rpc_client = RpcClient::new_with_commitment(url, CommitmentConfig {commitment});
+        let block_config = RpcBlockConfig {
+            commitment: Some(match commitment {
+                CommitmentLevel::Processed => CommitmentConfig::confirmed(),
+                commitment => CommitmentConfig {commitment},
+            }),
+            ..block_config()
+        };
+        let latest_slot = rpc_client.get_slot().await.map_err(|error| {
+            Error::RpcError(Box::new(error))
         })?;
-        Ok(Self{rpc_client, current_slot, block_config: block_config(), token})
+        let (current_slot, backfill) = match last_slot {
+            Some(last_slot) if last_slot < latest_slot => {
+                log::info!(
+                    "Backfill {} slots from {} to {}",
+                    latest_slot - last_slot,
+                    last_slot + 1,
+                    latest_slot
+                );
+                (last_slot + 1, Some((last_slot + 1, latest_slot)))
+            }
+            Some(last_slot) => (last_slot + 1, None),
+            None => (latest_slot, None),
+        };
+        Ok(Self{rpc_client, current_slot, backfill, block_config, token})
     }
 }
 
@@ -65,14 +95,20 @@ impl SourceStream for LiveStream {
             }
             match block_for_slot(self.current_slot, &self.rpc_client, self.block_config).await {
                 Ok(block) => {
-                    let block = Block::from(block);
+                    let block = Block::from((self.current_slot, block));
                     self.current_slot += 1;
+                    if let Some((start, end)) = self.backfill {
+                        if self.current_slot > end {
+                            log::info!("Backfill done, closed a gap of {} slots", end - start + 1);
+                            self.backfill = None;
+                        }
+                    }
                     log::debug!(
                         "Block: {} Transactions: {}",
                         block.height,
                         block.transactions.len()
                     );
-                    return SourceEvent::Next(block)
+                    return SourceEvent::Next(Box::new(block))
                 }
                 Err(error) => {
                     match error {
@@ -82,6 +118,8 @@ impl SourceStream for LiveStream {
                             continue
                         }
                         Error::SlotSkippedOrMissing(_) => {
+                            // A block which is missing rather than skipped shows up as a gap in
+                            // the aggregator, which rewinds the stream to fetch it again.
                             self.current_slot += 1;
                             log::warn!("Increment current slot: {}", error.to_string());
                             continue
@@ -94,4 +132,10 @@ impl SourceStream for LiveStream {
             }
         }
     }
+
+    fn rewind(&mut self, slot: Slot) -> bool {
+        log::info!("Rewind from slot {} to {}", self.current_slot, slot);
+        self.current_slot = slot;
+        true
+    }
 }
\ No newline at end of file
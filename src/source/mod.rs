@@ -1,41 +1,201 @@
 use crate::error::Error;
-use crate::types::{Block, Transaction};
-use solana_transaction_status::{EncodedTransaction, EncodedTransactionWithStatusMeta, UiConfirmedBlock, UiInstruction, UiMessage, UiParsedInstruction};
+use crate::source::parser::{
+    enabled_parsers, parse_instruction, parse_raw_instruction, InstructionContext, InstructionEvent,
+    InstructionParser, TokenAccounts,
+};
+use crate::types::{
+    parse_address, Address, BalanceChange, Block, CustomInstruction, Invocation, RawInstruction, Reward, RewardKind,
+    StakeInstruction, StakeInstructionKind, TokenBalanceChange, TokenTransfer, Transaction, TransactionMeta,
+    TransactionStatus, Vote,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use solana_sdk::clock::Slot;
+use solana_sdk::reward_type::RewardType;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::parse_accounts::ParsedAccountSource;
+use solana_transaction_status::{
+    EncodedTransaction, EncodedTransactionWithStatusMeta, Reward as UiReward, UiConfirmedBlock, UiInnerInstructions,
+    UiInstruction, UiLoadedAddresses, UiMessage, UiParsedInstruction, UiParsedMessage, UiTransactionTokenBalance,
+};
 
 pub mod benchmark;
 pub mod live;
+pub mod parser;
 
 pub enum SourceEvent {
-    Next(Block),
+    Next(Box<Block>),
     Failure(Error),
     EndOfStream,
 }
 
 pub trait SourceStream {
     fn next(&mut self) -> impl std::future::Future<Output = SourceEvent> + Send;
+    /// Continues the stream at `slot` to fetch blocks again which were missed. Returns `false` if
+    /// the stream can't go back.
+    fn rewind(&mut self, _slot: Slot) -> bool {
+        false
+    }
+}
+
+/// Collects the token balance changes of a transaction, ordered by the account index. Accounts
+/// without a known owner or with an invalid owner or mint are skipped.
+fn parse_token_balances(
+    account_keys: &[Address],
+    pre_token_balances: Vec<UiTransactionTokenBalance>,
+    post_token_balances: Vec<UiTransactionTokenBalance>,
+) -> Vec<TokenBalanceChange> {
+    let mut balances: BTreeMap<u8, (Option<UiTransactionTokenBalance>, Option<UiTransactionTokenBalance>)> =
+        BTreeMap::new();
+    for balance in pre_token_balances {
+        let account_index = balance.account_index;
+        balances.entry(account_index).or_default().0 = Some(balance);
+    }
+    for balance in post_token_balances {
+        let account_index = balance.account_index;
+        balances.entry(account_index).or_default().1 = Some(balance);
+    }
+    let amount = |balance: &Option<UiTransactionTokenBalance>| {
+        balance.as_ref().and_then(|balance| balance.ui_token_amount.amount.parse().ok())
+    };
+    let mut changes = Vec::with_capacity(balances.len());
+    for (account_index, (pre, post)) in balances {
+        let (Some(account), Some(balance)) = (
+            account_keys.get(account_index as usize),
+            post.as_ref().or(pre.as_ref()),
+        ) else {
+            continue;
+        };
+        let OptionSerializer::Some(owner) = &balance.owner else {
+            continue;
+        };
+        if parse_address(owner).is_err() || parse_address(&balance.mint).is_err() {
+            continue;
+        }
+        changes.push(
+            TokenBalanceChange {
+                account: account.clone(),
+                owner: owner.clone(),
+                mint: balance.mint.clone(),
+                decimals: balance.ui_token_amount.decimals,
+                pre_amount: amount(&pre),
+                post_amount: amount(&post),
+            }
+        );
+    }
+    changes
 }
 
-fn parse_instruction(instruction: &UiInstruction) -> Option<Transaction> {
-    if let UiInstruction::Parsed(parsed) = instruction {
-        if let UiParsedInstruction::Parsed(parsed_instruction) = parsed {
-            if parsed_instruction.program == "system" {
-                if parsed_instruction.parsed.get("type")?.as_str()? == "transfer" {
-                    let info = parsed_instruction.parsed.get("info")?.as_object()?;
-                    return Some(
-                        Transaction {
-                            sender: info.get("source")?.as_str()?.to_string(),
-                            receiver: info.get("destination")?.as_str()?.to_string(),
-                            amount: info.get("lamports")?.as_number()?.as_u64()?,
-                        }
-                    );
+#[derive(Default)]
+struct ParsedTransaction {
+    transactions: Vec<Transaction>,
+    balance_changes: Vec<BalanceChange>,
+    meta: Option<TransactionMeta>,
+    token_transfers: Vec<TokenTransfer>,
+    token_balance_changes: Vec<TokenBalanceChange>,
+    raw_instructions: Vec<RawInstruction>,
+    custom_instructions: Vec<CustomInstruction>,
+    memos: Vec<String>,
+    stake_instructions: Vec<StakeInstruction>,
+    votes: Vec<Vote>,
+}
+
+impl ParsedTransaction {
+    /// Parses the transfers of an outer instruction, or of an inner one if `invocation` is set.
+    /// Instructions the RPC interface couldn't parse are kept as raw instructions if no parser
+    /// handles them.
+    fn add_instruction(
+        &mut self,
+        parsers: &[Arc<dyn InstructionParser>],
+        context: &InstructionContext,
+        invocation: Option<Invocation>,
+        instruction: &UiInstruction,
+    ) {
+        let event = parse_instruction(parsers, context, instruction);
+        // An invalid address would make the storage reject the whole block.
+        if let Some(address) = event.as_ref().and_then(InstructionEvent::invalid_address) {
+            log::warn!(
+                "Skipping instruction {} of transaction {} with invalid address {}",
+                context.instruction_index,
+                context.signature,
+                address
+            );
+            return;
+        }
+        match event {
+            Some(InstructionEvent::Transfer(mut transaction)) => {
+                transaction.invocation = invocation;
+                self.transactions.push(transaction)
+            }
+            Some(InstructionEvent::TokenTransfer(mut transfer)) => {
+                transfer.invocation = invocation;
+                self.token_transfers.push(transfer)
+            }
+            Some(InstructionEvent::Memo(memo)) => self.memos.push(memo),
+            Some(InstructionEvent::Stake(mut stake_instruction)) => {
+                stake_instruction.invocation = invocation;
+                self.stake_instructions.push(stake_instruction)
+            }
+            Some(InstructionEvent::Vote(mut vote)) => {
+                vote.invocation = invocation;
+                self.votes.push(vote)
+            }
+            Some(InstructionEvent::Custom {program_id, name, data}) => {
+                self.custom_instructions.push(
+                    CustomInstruction {
+                        signature: context.signature.clone(),
+                        instruction_index: context.instruction_index,
+                        invocation,
+                        program_id,
+                        name,
+                        data,
+                    }
+                )
+            }
+            None => {
+                if let Some(mut raw_instruction) = parse_raw_instruction(context, instruction) {
+                    raw_instruction.invocation = invocation;
+                    self.raw_instructions.push(raw_instruction)
                 }
             }
         }
     }
-    None
 }
 
-fn parse_transaction(transaction: EncodedTransactionWithStatusMeta) -> Option<Vec<Transaction>> {
+/// All accounts of a transaction in the order its balances and compiled instructions refer to them
+/// by index. The accounts loaded from address lookup tables by versioned transactions follow the
+/// static ones, first the writable and then the readonly ones.
+fn account_keys(message: &UiParsedMessage, loaded_addresses: Option<UiLoadedAddresses>) -> Vec<Address> {
+    let mut account_keys: Vec<Address> = message.account_keys.iter()
+        .map(|account| account.pubkey.clone())
+        .collect();
+    // Parsed messages usually contain the loaded accounts already.
+    let resolved = message.account_keys.iter()
+        .any(|account| account.source == Some(ParsedAccountSource::LookupTable));
+    if let (false, Some(loaded_addresses)) = (resolved, loaded_addresses) {
+        account_keys.extend(loaded_addresses.writable);
+        account_keys.extend(loaded_addresses.readonly);
+    }
+    account_keys
+}
+
+/// The id of the program executing `instruction`, if it's a valid address.
+fn program_id(account_keys: &[Address], instruction: &UiInstruction) -> Option<Address> {
+    let program_id = match instruction {
+        UiInstruction::Compiled(instruction) => account_keys.get(instruction.program_id_index as usize).cloned(),
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(instruction)) => Some(instruction.program_id.clone()),
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(instruction)) => {
+            Some(instruction.program_id.clone())
+        }
+    }?;
+    parse_address(&program_id).is_ok().then_some(program_id)
+}
+
+fn parse_transaction(
+    parsers: &[Arc<dyn InstructionParser>],
+    transaction: EncodedTransactionWithStatusMeta,
+) -> Option<ParsedTransaction> {
+    let meta = transaction.meta;
     let transaction = match transaction.transaction {
         EncodedTransaction::Json(transaction) => {transaction}
         _ => return None
@@ -46,31 +206,527 @@ fn parse_transaction(transaction: EncodedTransactionWithStatusMeta) -> Option<Ve
         _ => return None,
     };
 
-    let mut transactions = Vec::new();
-    for instruction in &message.instructions {
-        if let Some(transaction) = parse_instruction(instruction) {
-            transactions.push(transaction)
+    // The first signature is the one identifying the transaction.
+    let signature = transaction.signatures.first()?;
+    let loaded_addresses = meta.as_ref().and_then(|meta| Option::from(meta.loaded_addresses.clone()));
+    let account_keys = account_keys(message, loaded_addresses);
+    // Balances and instructions refer to the accounts by index, so nothing can be parsed reliably
+    // if one of them is invalid.
+    if let Some(address) = account_keys.iter().find(|address| parse_address(address).is_err()) {
+        log::warn!("Skipping transaction {} with invalid account {}", signature, address);
+        return None;
+    }
+
+    let mut parsed = ParsedTransaction::default();
+    let mut inner_instructions: HashMap<usize, Vec<UiInstruction>> = HashMap::new();
+    if let Some(meta) = meta {
+        parsed.meta = Some(
+            TransactionMeta {
+                signature: signature.clone(),
+                fee: meta.fee,
+                // The first account is the one paying the fee.
+                fee_payer: account_keys.first()?.clone(),
+                status: match &meta.err {
+                    Some(error) => TransactionStatus::Failed(error.to_string()),
+                    None => TransactionStatus::Success,
+                },
+                compute_units_consumed: meta.compute_units_consumed.clone().into(),
+                memo: None,
+            }
+        );
+        for (index, account) in account_keys.iter().enumerate() {
+            if let (Some(pre_balance), Some(post_balance)) = (
+                meta.pre_balances.get(index),
+                meta.post_balances.get(index),
+            ) {
+                parsed.balance_changes.push(
+                    BalanceChange {
+                        address: account.clone(),
+                        pre_balance: *pre_balance,
+                        post_balance: *post_balance,
+                    }
+                );
+            }
+        }
+        parsed.token_balance_changes = parse_token_balances(
+            &account_keys,
+            Option::from(meta.pre_token_balances).unwrap_or_default(),
+            Option::from(meta.post_token_balances).unwrap_or_default(),
+        );
+        let inner: Option<Vec<UiInnerInstructions>> = meta.inner_instructions.into();
+        for inner in inner.unwrap_or_default() {
+            inner_instructions.entry(inner.index as usize).or_default().extend(inner.instructions);
+        }
+    }
+    let token_accounts: TokenAccounts = parsed.token_balance_changes.iter()
+        .map(|change| (change.account.clone(), (change.mint.clone(), change.decimals)))
+        .collect();
+    for (index, instruction) in message.instructions.iter().enumerate() {
+        let context = InstructionContext {
+            signature,
+            instruction_index: index,
+            token_accounts: &token_accounts,
+            account_keys: &account_keys,
+        };
+        parsed.add_instruction(parsers, &context, None, instruction);
+        // Cross-program invocations only show up in the inner instructions of the transaction meta.
+        let (Some(inner_instructions), Some(program_id)) = (
+            inner_instructions.get(&index),
+            program_id(&account_keys, instruction),
+        ) else {
+            continue;
+        };
+        for (inner_index, inner_instruction) in inner_instructions.iter().enumerate() {
+            let invocation = Invocation {program_id: program_id.clone(), inner_index};
+            parsed.add_instruction(parsers, &context, Some(invocation), inner_instruction);
+        }
+    }
+    // Merges move all lamports of the merged stake account.
+    for stake_instruction in &mut parsed.stake_instructions {
+        if stake_instruction.kind == StakeInstructionKind::Merge {
+            stake_instruction.amount = parsed.balance_changes.iter()
+                .find(|change| change.address == stake_instruction.stake_account)
+                .map_or(0, |change| change.pre_balance);
+        }
+    }
+    // The memos identify the whole transaction and not a specific transfer.
+    if let (Some(meta), false) = (parsed.meta.as_mut(), parsed.memos.is_empty()) {
+        meta.memo = Some(parsed.memos.join("; "));
+    }
+    Some(parsed)
+}
+
+/// Merges the balance changes of a transaction into the ones of the block, keeping the first pre
+/// balance and the last post balance of each account.
+fn merge_balance_changes(
+    balance_changes: &mut Vec<BalanceChange>,
+    positions: &mut HashMap<Address, usize>,
+    transaction_changes: Vec<BalanceChange>,
+) {
+    for change in transaction_changes {
+        match positions.get(&change.address) {
+            Some(position) => {
+                balance_changes[*position].post_balance = change.post_balance;
+            }
+            None => {
+                positions.insert(change.address.clone(), balance_changes.len());
+                balance_changes.push(change);
+            }
         }
     }
-    Some(transactions)
 }
 
+/// Merges the token balance changes of a transaction into the ones of the block, keeping the first
+/// pre amount and the last post amount of each token account.
+fn merge_token_balance_changes(
+    token_balance_changes: &mut Vec<TokenBalanceChange>,
+    positions: &mut HashMap<Address, usize>,
+    transaction_changes: Vec<TokenBalanceChange>,
+) {
+    for change in transaction_changes {
+        match positions.get(&change.account) {
+            Some(position) => {
+                token_balance_changes[*position].post_amount = change.post_amount;
+            }
+            None => {
+                positions.insert(change.account.clone(), token_balance_changes.len());
+                token_balance_changes.push(change);
+            }
+        }
+    }
+}
 
-impl From<UiConfirmedBlock> for Block {
-    fn from(block: UiConfirmedBlock) -> Self {
-        let mut transactions: Vec<Transaction> = Vec::new();
-        if let Some(block_transactions) = block.transactions {
-            for transaction in block_transactions {
-                if let Some(mut parsed) = parse_transaction(transaction) {
-                    transactions.append(&mut parsed);
+/// Merges the block rewards into the balance changes of the block. Fee rewards are credited after
+/// all transactions, all other rewards before the first one.
+fn merge_rewards(
+    balance_changes: &mut Vec<BalanceChange>,
+    positions: &mut HashMap<Address, usize>,
+    rewards: &[Reward],
+) {
+    for reward in rewards {
+        let pre_balance = reward.post_balance.saturating_add_signed(-reward.lamports);
+        match positions.get(&reward.address) {
+            Some(position) => {
+                if reward.kind == Some(RewardKind::Fee) {
+                    balance_changes[*position].post_balance = reward.post_balance;
+                } else {
+                    balance_changes[*position].pre_balance = pre_balance;
                 }
             }
+            None => {
+                positions.insert(reward.address.clone(), balance_changes.len());
+                balance_changes.push(
+                    BalanceChange {
+                        address: reward.address.clone(),
+                        pre_balance,
+                        post_balance: reward.post_balance,
+                    }
+                );
+            }
+        }
+    }
+}
+
+/// Rewards of invalid addresses are skipped.
+fn parse_reward(reward: UiReward) -> Option<Reward> {
+    parse_address(&reward.pubkey).ok()?;
+    Some(Reward {
+        address: reward.pubkey,
+        lamports: reward.lamports,
+        post_balance: reward.post_balance,
+        kind: reward.reward_type.map(|kind| match kind {
+            RewardType::Fee => RewardKind::Fee,
+            RewardType::Rent => RewardKind::Rent,
+            RewardType::Staking => RewardKind::Staking,
+            RewardType::Voting => RewardKind::Voting,
+        }),
+        commission: reward.commission,
+    })
+}
+
+impl From<(Slot, UiConfirmedBlock)> for Block {
+    fn from((slot, block): (Slot, UiConfirmedBlock)) -> Self {
+        parse_block(slot, block, &enabled_parsers())
+    }
+}
+
+/// Parses the block at `slot` with the given `parsers`.
+fn parse_block(slot: Slot, block: UiConfirmedBlock, parsers: &[Arc<dyn InstructionParser>]) -> Block {
+    let mut transactions: Vec<Transaction> = Vec::new();
+    let mut balance_changes: Vec<BalanceChange> = Vec::new();
+    let mut transaction_metas: Vec<TransactionMeta> = Vec::new();
+    let mut token_transfers: Vec<TokenTransfer> = Vec::new();
+    let mut token_balance_changes: Vec<TokenBalanceChange> = Vec::new();
+    let mut raw_instructions: Vec<RawInstruction> = Vec::new();
+    let mut custom_instructions: Vec<CustomInstruction> = Vec::new();
+    let mut stake_instructions: Vec<StakeInstruction> = Vec::new();
+    let mut votes: Vec<Vote> = Vec::new();
+    let mut token_positions: HashMap<Address, usize> = HashMap::new();
+    let mut positions: HashMap<Address, usize> = HashMap::new();
+    if let Some(block_transactions) = block.transactions {
+        for transaction in block_transactions {
+            if let Some(mut parsed) = parse_transaction(parsers, transaction) {
+                transactions.append(&mut parsed.transactions);
+                merge_balance_changes(&mut balance_changes, &mut positions, parsed.balance_changes);
+                transaction_metas.extend(parsed.meta);
+                token_transfers.append(&mut parsed.token_transfers);
+                merge_token_balance_changes(
+                    &mut token_balance_changes,
+                    &mut token_positions,
+                    parsed.token_balance_changes,
+                );
+                raw_instructions.append(&mut parsed.raw_instructions);
+                custom_instructions.append(&mut parsed.custom_instructions);
+                stake_instructions.append(&mut parsed.stake_instructions);
+                votes.append(&mut parsed.votes);
+            }
+        }
+    };
+    let rewards: Vec<Reward> = block.rewards.unwrap_or_default().into_iter().filter_map(parse_reward).collect();
+    merge_rewards(&mut balance_changes, &mut positions, &rewards);
+    Block {
+        slot,
+        parent_slot: block.parent_slot,
+        height: block.block_height.unwrap(),
+        hash: block.blockhash,
+        previous_blockhash: block.previous_blockhash,
+        transactions,
+        balance_changes,
+        transaction_metas,
+        token_transfers,
+        token_balance_changes,
+        raw_instructions,
+        custom_instructions,
+        stake_instructions,
+        votes,
+        rewards,
+        timestamp: block.block_time.unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::Memory;
+    use crate::storage::Storage;
+    use crate::types::SystemInstructionKind;
+    use serde_json::{json, Value};
+    use solana_sdk::pubkey::Pubkey;
+
+    /// A valid address for the short `name`s used in the tests.
+    pub fn address(name: &str) -> Address {
+        let mut bytes = [0; 32];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Pubkey::new_from_array(bytes).to_string()
+    }
+
+    #[test]
+    fn test_parse_inner_instructions() {
+        let transfer = |source: &str, destination: &str| json!({
+            "program": "system",
+            "programId": "11111111111111111111111111111111",
+            "parsed": {
+                "type": "transfer",
+                "info": {"source": address(source), "destination": address(destination), "lamports": 5},
+            },
+            "stackHeight": 2,
+        });
+        let account = |name: &str| {
+            json!({"pubkey": address(name), "writable": true, "signer": false, "source": "transaction"})
+        };
+        let transaction: EncodedTransactionWithStatusMeta = serde_json::from_value(json!({
+            "transaction": {
+                "signatures": ["s"],
+                "message": {
+                    "accountKeys": [account("a"), account("b"), account("c"), account("p")],
+                    "recentBlockhash": "h",
+                    "instructions": [
+                        {
+                            "programId": address("p"),
+                            "accounts": [address("a"), address("b")],
+                            "data": "",
+                            "stackHeight": null,
+                        },
+                        transfer("a", "c"),
+                    ],
+                },
+            },
+            "meta": {
+                "err": null,
+                "status": {"Ok": null},
+                "fee": 5000,
+                "preBalances": [100, 0, 0, 1],
+                "postBalances": [85, 10, 0, 1],
+                "innerInstructions": [{"index": 0, "instructions": [transfer("a", "b"), transfer("b", "c")]}],
+            },
+        })).unwrap();
+        let parsed = parse_transaction(&enabled_parsers(), transaction).unwrap();
+        let expected = |instruction_index, invocation: Option<(&str, usize)>, sender: &str, receiver: &str| {
+            Transaction {
+                signature: "s".to_string(),
+                instruction_index,
+                invocation: invocation.map(|(program_id, inner_index)| Invocation {
+                    program_id: address(program_id),
+                    inner_index,
+                }),
+                kind: SystemInstructionKind::Transfer,
+                sender: address(sender),
+                receiver: address(receiver),
+                amount: 5,
+            }
+        };
+        // Inner instructions follow the outer instruction which invoked them.
+        assert_eq!(
+            parsed.transactions,
+            [
+                expected(0, Some(("p", 0)), "a", "b"),
+                expected(0, Some(("p", 1)), "b", "c"),
+                expected(1, None, "a", "c"),
+            ]
+        );
+        // The RPC interface couldn't parse the outer instruction of the unknown program.
+        assert_eq!(
+            parsed.raw_instructions,
+            [
+                RawInstruction {
+                    signature: "s".to_string(),
+                    instruction_index: 0,
+                    invocation: None,
+                    program_id: address("p"),
+                    accounts: vec![address("a"), address("b")],
+                    data: "".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_custom_instruction() {
+        struct SwapParser;
+
+        impl InstructionParser for SwapParser {
+            fn name(&self) -> &str {
+                "swap"
+            }
+            fn parse(&self, _: &InstructionContext, instruction: &UiInstruction) -> Option<InstructionEvent> {
+                let UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(instruction)) = instruction else {
+                    return None;
+                };
+                Some(InstructionEvent::Custom {
+                    program_id: instruction.program_id.clone(),
+                    name: "swap".to_string(),
+                    data: json!({"accounts": instruction.accounts.len()}),
+                })
+            }
+        }
+
+        let instruction = |stack_height: Option<u32>| json!({
+            "programId": address("p"),
+            "accounts": [address("a"), address("b")],
+            "data": "3Bxs",
+            "stackHeight": stack_height,
+        });
+        let transaction: EncodedTransactionWithStatusMeta = serde_json::from_value(json!({
+            "transaction": {
+                "signatures": ["s"],
+                "message": {
+                    "accountKeys": [
+                        {"pubkey": address("a"), "writable": true, "signer": true, "source": "transaction"},
+                        {"pubkey": address("p"), "writable": false, "signer": false, "source": "transaction"},
+                    ],
+                    "recentBlockhash": "h",
+                    "instructions": [instruction(None)],
+                },
+            },
+            "meta": {
+                "err": null,
+                "status": {"Ok": null},
+                "fee": 5000,
+                "preBalances": [100, 1],
+                "postBalances": [95, 1],
+                "innerInstructions": [{"index": 0, "instructions": [instruction(Some(2))]}],
+            },
+        })).unwrap();
+        let parsers: [Arc<dyn InstructionParser>; 1] = [Arc::new(SwapParser)];
+        let parsed = parse_transaction(&parsers, transaction).unwrap();
+        let expected = |invocation: Option<Invocation>| CustomInstruction {
+            signature: "s".to_string(),
+            instruction_index: 0,
+            invocation,
+            program_id: address("p"),
+            name: "swap".to_string(),
+            data: json!({"accounts": 2}),
+        };
+        assert_eq!(
+            parsed.custom_instructions,
+            [expected(None), expected(Some(Invocation {program_id: address("p"), inner_index: 0}))]
+        );
+        // The instructions handled by a parser are not kept as raw instructions as well.
+        assert!(parsed.raw_instructions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_parse_invalid_address() {
+        struct InvalidParser;
+
+        impl InstructionParser for InvalidParser {
+            fn name(&self) -> &str {
+                "invalid"
+            }
+            fn parse(&self, context: &InstructionContext, instruction: &UiInstruction) -> Option<InstructionEvent> {
+                let UiInstruction::Parsed(UiParsedInstruction::Parsed(instruction)) = instruction else {
+                    return None;
+                };
+                (instruction.program == "invalid").then(|| InstructionEvent::Transfer(Transaction {
+                    signature: context.signature.clone(),
+                    instruction_index: context.instruction_index,
+                    invocation: None,
+                    kind: SystemInstructionKind::Transfer,
+                    sender: address("a"),
+                    receiver: "invalid".to_string(),
+                    amount: 1,
+                }))
+            }
+        }
+
+        let instruction = |program: &str| json!({
+            "program": program,
+            "programId": address("p"),
+            "parsed": {
+                "type": "transfer",
+                "info": {"source": address("a"), "destination": address("b"), "lamports": 5},
+            },
+            "stackHeight": null,
+        });
+        let block: UiConfirmedBlock = serde_json::from_value(json!({
+            "previousBlockhash": "h0",
+            "blockhash": "h1",
+            "parentSlot": 0,
+            "transactions": [{
+                "transaction": {
+                    "signatures": ["s"],
+                    "message": {
+                        "accountKeys": [
+                            {"pubkey": address("a"), "writable": true, "signer": true, "source": "transaction"},
+                            {"pubkey": address("b"), "writable": true, "signer": false, "source": "transaction"},
+                        ],
+                        "recentBlockhash": "h0",
+                        "instructions": [instruction("invalid"), instruction("system")],
+                    },
+                },
+                "meta": {
+                    "err": null,
+                    "status": {"Ok": null},
+                    "fee": 5000,
+                    "preBalances": [100, 0],
+                    "postBalances": [90, 5],
+                },
+            }],
+            "blockTime": 0,
+            "blockHeight": 1,
+        })).unwrap();
+        let mut parsers = enabled_parsers();
+        parsers.insert(0, Arc::new(InvalidParser));
+        let block = parse_block(1, block, &parsers);
+        // Only the event with the invalid address is skipped, the rest of the block is kept.
+        assert_eq!(block.transactions.len(), 1);
+        assert_eq!(block.transactions[0].instruction_index, 1);
+        let mut storage = Memory::default();
+        storage.add_block(block.clone()).await.unwrap();
+        assert_eq!(storage.get_last_block().await.unwrap(), Some(block));
+    }
+
+    #[test]
+    fn test_parse_loaded_addresses() {
+        let account = |name: &str, source: &str| {
+            json!({"pubkey": address(name), "writable": true, "signer": false, "source": source})
+        };
+        let transaction = |account_keys: Value| -> EncodedTransactionWithStatusMeta {
+            serde_json::from_value(json!({
+                "transaction": {
+                    "signatures": ["s"],
+                    "message": {
+                        "accountKeys": account_keys,
+                        "recentBlockhash": "h",
+                        "instructions": [{"programIdIndex": 3, "accounts": [0, 2], "data": "3Bxs"}],
+                        "addressTableLookups": [
+                            {"accountKey": address("t"), "writableIndexes": [0], "readonlyIndexes": [1]},
+                        ],
+                    },
+                },
+                "meta": {
+                    "err": null,
+                    "status": {"Ok": null},
+                    "fee": 5000,
+                    "preBalances": [100, 1, 0, 1],
+                    "postBalances": [95, 1, 5, 1],
+                    "preTokenBalances": [],
+                    "postTokenBalances": [{
+                        "accountIndex": 2,
+                        "mint": address("m"),
+                        "owner": address("o"),
+                        "uiTokenAmount": {"amount": "7", "decimals": 0, "uiAmount": 7.0, "uiAmountString": "7"},
+                    }],
+                    "loadedAddresses": {"writable": [address("w")], "readonly": [address("r")]},
+                },
+                "version": 0,
+            })).unwrap()
         };
-        Self {
-            height: block.block_height.unwrap(),
-            hash: block.blockhash,
-            transactions,
-            timestamp: block.block_time.unwrap(),
+        // The loaded accounts are only part of the message if the RPC interface resolved them.
+        let static_keys = json!([account("a", "transaction"), account("p", "transaction")]);
+        let resolved_keys = json!([
+            account("a", "transaction"),
+            account("p", "transaction"),
+            account("w", "lookupTable"),
+            account("r", "lookupTable"),
+        ]);
+        for account_keys in [static_keys, resolved_keys] {
+            let parsed = parse_transaction(&enabled_parsers(), transaction(account_keys)).unwrap();
+            let addresses: Vec<Address> = parsed.balance_changes.into_iter().map(|change| change.address).collect();
+            assert_eq!(addresses, ["a", "p", "w", "r"].map(address));
+            assert_eq!(parsed.token_balance_changes[0].account, address("w"));
+            assert_eq!(parsed.raw_instructions[0].program_id, address("r"));
+            assert_eq!(parsed.raw_instructions[0].accounts, ["a", "w"].map(address));
         }
     }
 }
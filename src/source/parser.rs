@@ -0,0 +1,848 @@
+use crate::error::{Error, Result};
+use crate::types::{
+    parse_address, Address, RawInstruction, Signature, StakeInstruction, StakeInstructionKind, SystemInstructionKind,
+    TokenTransfer, TokenTransferKind, Transaction, Vote,
+};
+use serde_json::{Map, Value};
+use solana_transaction_status::{UiInstruction, UiParsedInstruction};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, RwLock};
+
+/// The mint and decimals of the token accounts of a transaction, taken from its token balances.
+pub type TokenAccounts = HashMap<Address, (Address, u8)>;
+
+/// The transaction an instruction is parsed from.
+pub struct InstructionContext<'a> {
+    pub signature: &'a Signature,
+    /// The index of the outer instruction for inner instructions.
+    pub instruction_index: usize,
+    pub token_accounts: &'a TokenAccounts,
+    /// The accounts compiled instructions refer to by index.
+    pub account_keys: &'a [Address],
+}
+
+/// What a parser extracted from an instruction.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InstructionEvent {
+    Transfer(Transaction),
+    TokenTransfer(TokenTransfer),
+    Memo(String),
+    Stake(StakeInstruction),
+    Vote(Vote),
+    /// Anything the built-in events don't cover, stored next to the raw instructions as a
+    /// [`CustomInstruction`](crate::types::CustomInstruction) of `program_id`.
+    Custom {program_id: Address, name: String, data: Value},
+}
+
+impl InstructionEvent {
+    /// The first address of the event which is not a valid public key, if any. Parsers registered
+    /// from outside are free to return anything, the built-in ones only return valid addresses.
+    pub fn invalid_address(&self) -> Option<&Address> {
+        let addresses: Vec<&Address> = match self {
+            InstructionEvent::Transfer(transaction) => Vec::from([&transaction.sender, &transaction.receiver]),
+            InstructionEvent::TokenTransfer(transfer) => std::iter::once(&transfer.mint)
+                .chain(&transfer.source)
+                .chain(&transfer.destination)
+                .collect(),
+            InstructionEvent::Memo(_) => Vec::new(),
+            InstructionEvent::Stake(instruction) => std::iter::once(&instruction.stake_account)
+                .chain(&instruction.vote_account)
+                .chain(&instruction.destination)
+                .collect(),
+            InstructionEvent::Vote(vote) => Vec::from([&vote.vote_account, &vote.authority]),
+            InstructionEvent::Custom {program_id, ..} => Vec::from([program_id]),
+        };
+        addresses.into_iter().find(|address| parse_address(address).is_err())
+    }
+}
+
+/// Parses the instructions of one or more programs. Parsers are registered via
+/// [`register_parser`] and consulted in the order of their registration for every instruction
+/// until one of them returns an event.
+pub trait InstructionParser: Send + Sync {
+    /// The unique name to enable or disable the parser with.
+    fn name(&self) -> &str;
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+    /// Returns `None` if `instruction` is not one the parser is responsible for.
+    fn parse(&self, context: &InstructionContext, instruction: &UiInstruction) -> Option<InstructionEvent>;
+}
+
+/// The address in the field `key` of a parsed instruction's `info`, if it's a valid public key.
+fn info_address(info: &Map<String, Value>, key: &str) -> Option<Address> {
+    Some(parse_address(info.get(key)?.as_str()?).ok()?.to_string())
+}
+
+fn parse_system_instruction(context: &InstructionContext, instruction: &UiInstruction) -> Option<Transaction> {
+    if let UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed_instruction)) = instruction {
+        if parsed_instruction.program == "system" {
+            let info = parsed_instruction.parsed.get("info")?.as_object()?;
+            let (kind, sender, receiver) = match parsed_instruction.parsed.get("type")?.as_str()? {
+                "transfer" => (SystemInstructionKind::Transfer, "source", "destination"),
+                "transferWithSeed" => (SystemInstructionKind::TransferWithSeed, "source", "destination"),
+                "createAccount" => (SystemInstructionKind::CreateAccount, "source", "newAccount"),
+                "createAccountWithSeed" => (SystemInstructionKind::CreateAccountWithSeed, "source", "newAccount"),
+                "withdrawFromNonce" => (SystemInstructionKind::WithdrawFromNonce, "nonceAccount", "destination"),
+                "assign" => (SystemInstructionKind::Assign, "account", "account"),
+                "assignWithSeed" => (SystemInstructionKind::AssignWithSeed, "account", "account"),
+                "allocate" => (SystemInstructionKind::Allocate, "account", "account"),
+                "allocateWithSeed" => (SystemInstructionKind::AllocateWithSeed, "account", "account"),
+                "advanceNonce" => (SystemInstructionKind::AdvanceNonce, "nonceAccount", "nonceAccount"),
+                "initializeNonce" => (SystemInstructionKind::InitializeNonce, "nonceAccount", "nonceAccount"),
+                "authorizeNonce" => (SystemInstructionKind::AuthorizeNonce, "nonceAccount", "nonceAccount"),
+                "upgradeNonce" => (SystemInstructionKind::UpgradeNonce, "nonceAccount", "nonceAccount"),
+                _ => return None,
+            };
+            let amount = match info.get("lamports") {
+                Some(lamports) => lamports.as_number()?.as_u64()?,
+                None => 0,
+            };
+            return Some(
+                Transaction {
+                    signature: context.signature.clone(),
+                    instruction_index: context.instruction_index,
+                    invocation: None,
+                    kind,
+                    sender: info_address(info, sender)?,
+                    receiver: info_address(info, receiver)?,
+                    amount,
+                }
+            );
+        }
+    }
+    None
+}
+
+fn parse_token_instruction(context: &InstructionContext, instruction: &UiInstruction) -> Option<TokenTransfer> {
+    let UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed_instruction)) = instruction else {
+        return None;
+    };
+    // Token-2022 instructions are parsed as `spl-token` too.
+    if parsed_instruction.program != "spl-token" {
+        return None;
+    }
+    let info = parsed_instruction.parsed.get("info")?.as_object()?;
+    let address = |key: &str| info_address(info, key);
+    let (kind, source, destination) = match parsed_instruction.parsed.get("type")?.as_str()? {
+        "transfer" | "transferChecked" => {
+            (TokenTransferKind::Transfer, Some(address("source")?), Some(address("destination")?))
+        }
+        "mintTo" | "mintToChecked" => (TokenTransferKind::MintTo, None, Some(address("account")?)),
+        "burn" | "burnChecked" => (TokenTransferKind::Burn, Some(address("account")?), None),
+        _ => return None,
+    };
+    // The checked variants carry the amount together with the decimals, plain transfers don't
+    // even name the mint, so both fall back to the token balances of the transaction.
+    let token_amount = info.get("tokenAmount");
+    let amount = token_amount.map_or(info.get("amount"), |token_amount| token_amount.get("amount"))?;
+    let token_account = context.token_accounts.get(source.as_ref().or(destination.as_ref())?);
+    let decimals = match token_amount.and_then(|token_amount| token_amount.get("decimals")?.as_u64()) {
+        Some(decimals) => u8::try_from(decimals).ok()?,
+        None => token_account?.1,
+    };
+    Some(
+        TokenTransfer {
+            signature: context.signature.clone(),
+            instruction_index: context.instruction_index,
+            invocation: None,
+            kind,
+            mint: address("mint").or_else(|| Some(token_account?.0.clone()))?,
+            decimals,
+            source,
+            destination,
+            amount: amount.as_str()?.parse().ok()?,
+        }
+    )
+}
+
+fn parse_stake_instruction(context: &InstructionContext, instruction: &UiInstruction) -> Option<StakeInstruction> {
+    let UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed_instruction)) = instruction else {
+        return None;
+    };
+    if parsed_instruction.program != "stake" {
+        return None;
+    }
+    let info = parsed_instruction.parsed.get("info")?.as_object()?;
+    let address = |key: &str| info_address(info, key);
+    let (kind, stake_account, vote_account, destination) = match parsed_instruction.parsed.get("type")?.as_str()? {
+        "initialize" | "initializeChecked" => (StakeInstructionKind::Initialize, "stakeAccount", None, None),
+        "delegate" => (StakeInstructionKind::Delegate, "stakeAccount", Some("voteAccount"), None),
+        "deactivate" | "deactivateDelinquent" => (StakeInstructionKind::Deactivate, "stakeAccount", None, None),
+        "withdraw" => (StakeInstructionKind::Withdraw, "stakeAccount", None, Some("destination")),
+        "split" => (StakeInstructionKind::Split, "stakeAccount", None, Some("newSplitAccount")),
+        // The merged lamports are only known from the balances of the transaction.
+        "merge" => (StakeInstructionKind::Merge, "source", None, Some("destination")),
+        _ => return None,
+    };
+    let amount = match info.get("lamports") {
+        Some(lamports) => lamports.as_u64()?,
+        None => 0,
+    };
+    Some(
+        StakeInstruction {
+            signature: context.signature.clone(),
+            instruction_index: context.instruction_index,
+            invocation: None,
+            kind,
+            stake_account: address(stake_account)?,
+            vote_account: match vote_account {
+                Some(vote_account) => Some(address(vote_account)?),
+                None => None,
+            },
+            destination: match destination {
+                Some(destination) => Some(address(destination)?),
+                None => None,
+            },
+            amount,
+        }
+    )
+}
+
+fn parse_vote_instruction(context: &InstructionContext, instruction: &UiInstruction) -> Option<Vote> {
+    let UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed_instruction)) = instruction else {
+        return None;
+    };
+    if parsed_instruction.program != "vote" {
+        return None;
+    }
+    let info = parsed_instruction.parsed.get("info")?.as_object()?;
+    // Plain votes list the voted slots, the others the lockouts of the whole tower.
+    let slot = match parsed_instruction.parsed.get("type")?.as_str()? {
+        "vote" | "voteSwitch" => info.get("vote")?.get("slots")?.as_array()?.last().cloned(),
+        "updatevotestate" | "updatevotestateswitch" | "compactupdatevotestate" | "compactupdatevotestateswitch" => {
+            info.get("voteStateUpdate")?.get("lockouts")?.as_array()?.last()?.get("slot").cloned()
+        }
+        "towersync" | "towersyncswitch" => {
+            info.get("towerSync")?.get("lockouts")?.as_array()?.last()?.get("slot").cloned()
+        }
+        _ => return None,
+    };
+    Some(
+        Vote {
+            signature: context.signature.clone(),
+            instruction_index: context.instruction_index,
+            invocation: None,
+            vote_account: info_address(info, "voteAccount")?,
+            authority: info_address(info, "voteAuthority")?,
+            slot: slot.and_then(|slot| slot.as_u64()),
+        }
+    )
+}
+
+pub struct SystemParser;
+
+impl InstructionParser for SystemParser {
+    fn name(&self) -> &str {
+        "system"
+    }
+
+    fn parse(&self, context: &InstructionContext, instruction: &UiInstruction) -> Option<InstructionEvent> {
+        parse_system_instruction(context, instruction).map(InstructionEvent::Transfer)
+    }
+}
+
+pub struct TokenParser;
+
+impl InstructionParser for TokenParser {
+    fn name(&self) -> &str {
+        "spl-token"
+    }
+
+    fn parse(&self, context: &InstructionContext, instruction: &UiInstruction) -> Option<InstructionEvent> {
+        parse_token_instruction(context, instruction).map(InstructionEvent::TokenTransfer)
+    }
+}
+
+/// Parses the SPL Memo v1 and v2 instructions.
+pub struct MemoParser;
+
+impl InstructionParser for MemoParser {
+    fn name(&self) -> &str {
+        "spl-memo"
+    }
+
+    fn parse(&self, _context: &InstructionContext, instruction: &UiInstruction) -> Option<InstructionEvent> {
+        let UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed_instruction)) = instruction else {
+            return None;
+        };
+        if parsed_instruction.program != "spl-memo" {
+            return None;
+        }
+        Some(InstructionEvent::Memo(parsed_instruction.parsed.as_str()?.to_string()))
+    }
+}
+
+pub struct StakeParser;
+
+impl InstructionParser for StakeParser {
+    fn name(&self) -> &str {
+        "stake"
+    }
+
+    fn parse(&self, context: &InstructionContext, instruction: &UiInstruction) -> Option<InstructionEvent> {
+        parse_stake_instruction(context, instruction).map(InstructionEvent::Stake)
+    }
+}
+
+pub struct VoteParser;
+
+impl InstructionParser for VoteParser {
+    fn name(&self) -> &str {
+        "vote"
+    }
+
+    fn parse(&self, context: &InstructionContext, instruction: &UiInstruction) -> Option<InstructionEvent> {
+        parse_vote_instruction(context, instruction).map(InstructionEvent::Vote)
+    }
+}
+
+struct RegisteredParser {
+    parser: Arc<dyn InstructionParser>,
+    enabled: bool,
+}
+
+/// The parsers in the order they are consulted, together with whether they are enabled.
+pub struct ParserRegistry {
+    parsers: Vec<RegisteredParser>,
+}
+
+impl Default for ParserRegistry {
+    /// A registry with the built-in parsers.
+    fn default() -> Self {
+        let mut registry = Self {parsers: Vec::new()};
+        registry.register(SystemParser);
+        registry.register(TokenParser);
+        registry.register(MemoParser);
+        registry.register(StakeParser);
+        registry.register(VoteParser);
+        registry
+    }
+}
+
+impl ParserRegistry {
+    /// Registers `parser` behind the already registered ones, or replaces the one with the same
+    /// name.
+    pub fn register<Parser: InstructionParser + 'static>(&mut self, parser: Parser) {
+        let parser = RegisteredParser {enabled: parser.enabled_by_default(), parser: Arc::new(parser)};
+        match self.parsers.iter_mut().find(|registered| registered.parser.name() == parser.parser.name()) {
+            Some(registered) => *registered = parser,
+            None => self.parsers.push(parser),
+        }
+    }
+
+    /// Enables or disables the registered parser with `name`.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<()> {
+        let registered = self.parsers.iter_mut()
+            .find(|registered| registered.parser.name() == name)
+            .ok_or_else(|| Error::UnknownParser(name.to_string()))?;
+        registered.enabled = enabled;
+        Ok(())
+    }
+
+    /// The names of all registered parsers together with whether they are enabled.
+    pub fn parsers(&self) -> Vec<(String, bool)> {
+        self.parsers.iter()
+            .map(|registered| (registered.parser.name().to_string(), registered.enabled))
+            .collect()
+    }
+
+    /// The currently enabled parsers, in the order they are consulted.
+    pub fn enabled_parsers(&self) -> Vec<Arc<dyn InstructionParser>> {
+        self.parsers.iter()
+            .filter(|registered| registered.enabled)
+            .map(|registered| registered.parser.clone())
+            .collect()
+    }
+}
+
+/// The registry the sources parse the blocks with.
+static PARSERS: LazyLock<RwLock<ParserRegistry>> = LazyLock::new(RwLock::default);
+
+/// Registers `parser` with the global registry, see [`ParserRegistry::register`].
+pub fn register_parser<Parser: InstructionParser + 'static>(parser: Parser) {
+    PARSERS.write().unwrap().register(parser)
+}
+
+/// Enables or disables the parser with `name` in the global registry.
+pub fn set_parser_enabled(name: &str, enabled: bool) -> Result<()> {
+    PARSERS.write().unwrap().set_enabled(name, enabled)
+}
+
+/// The names of all parsers of the global registry together with whether they are enabled.
+pub fn parsers() -> Vec<(String, bool)> {
+    PARSERS.read().unwrap().parsers()
+}
+
+/// The currently enabled parsers of the global registry, in the order they are consulted.
+pub fn enabled_parsers() -> Vec<Arc<dyn InstructionParser>> {
+    PARSERS.read().unwrap().enabled_parsers()
+}
+
+/// Parses `instruction` with the first of `parsers` which is responsible for it.
+pub fn parse_instruction(
+    parsers: &[Arc<dyn InstructionParser>],
+    context: &InstructionContext,
+    instruction: &UiInstruction,
+) -> Option<InstructionEvent> {
+    parsers.iter().find_map(|parser| parser.parse(context, instruction))
+}
+
+/// Captures `instruction` as it is if the RPC interface couldn't parse it, which is the case for
+/// programs it doesn't know.
+pub fn parse_raw_instruction(context: &InstructionContext, instruction: &UiInstruction) -> Option<RawInstruction> {
+    let (program_id, accounts, data) = match instruction {
+        UiInstruction::Compiled(instruction) => {
+            let account_key = |index: u8| context.account_keys.get(index as usize).cloned();
+            (
+                account_key(instruction.program_id_index)?,
+                instruction.accounts.iter().map(|index| account_key(*index)).collect::<Option<Vec<_>>>()?,
+                &instruction.data,
+            )
+        }
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(instruction)) => {
+            let mut addresses = std::iter::once(&instruction.program_id).chain(&instruction.accounts);
+            if addresses.any(|address| parse_address(address).is_err()) {
+                return None;
+            }
+            (instruction.program_id.clone(), instruction.accounts.clone(), &instruction.data)
+        }
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(_)) => return None,
+    };
+    Some(
+        RawInstruction {
+            signature: context.signature.clone(),
+            instruction_index: context.instruction_index,
+            invocation: None,
+            program_id,
+            accounts,
+            data: data.clone(),
+        }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::tests::address;
+    use serde_json::{json, Value};
+
+    fn parsed_instruction(program: &str, program_id: &str, instruction_type: &str, info: Value) -> UiInstruction {
+        serde_json::from_value(json!({
+            "program": program,
+            "programId": program_id,
+            "parsed": {"type": instruction_type, "info": info},
+            "stackHeight": null,
+        })).unwrap()
+    }
+
+    fn token_instruction(instruction_type: &str, info: Value) -> UiInstruction {
+        parsed_instruction("spl-token", "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb", instruction_type, info)
+    }
+
+    #[test]
+    fn test_parse_instruction() {
+        let signature = "s".to_string();
+        let parse = |instruction_type: &str, info: Value| {
+            let instruction = parsed_instruction("system", "11111111111111111111111111111111", instruction_type, info);
+            let token_accounts = TokenAccounts::new();
+            let context = InstructionContext {
+                signature: &signature,
+                instruction_index: 2,
+                token_accounts: &token_accounts,
+                account_keys: &[],
+            };
+            parse_system_instruction(&context, &instruction)
+        };
+        let expected = |kind, sender: &str, receiver: &str, amount| {
+            Some(Transaction {
+                signature: signature.clone(),
+                instruction_index: 2,
+                invocation: None,
+                kind,
+                sender: address(sender),
+                receiver: address(receiver),
+                amount,
+            })
+        };
+        assert_eq!(
+            parse("transfer", json!({"source": address("a"), "destination": address("b"), "lamports": 5})),
+            expected(SystemInstructionKind::Transfer, "a", "b", 5)
+        );
+        assert_eq!(
+            parse("createAccount", json!({
+                "source": address("a"),
+                "newAccount": address("b"),
+                "lamports": 5,
+                "space": 0,
+                "owner": address("o"),
+            })),
+            expected(SystemInstructionKind::CreateAccount, "a", "b", 5)
+        );
+        assert_eq!(
+            parse("withdrawFromNonce", json!({
+                "nonceAccount": address("n"),
+                "destination": address("b"),
+                "recentBlockhashesSysvar": address("r"),
+                "rentSysvar": address("s"),
+                "nonceAuthority": address("a"),
+                "lamports": 5,
+            })),
+            expected(SystemInstructionKind::WithdrawFromNonce, "n", "b", 5)
+        );
+        // Instructions without lamports only affect a single account.
+        assert_eq!(
+            parse("assign", json!({"account": address("a"), "owner": address("o")})),
+            expected(SystemInstructionKind::Assign, "a", "a", 0)
+        );
+        assert_eq!(
+            parse("advanceNonce", json!({
+                "nonceAccount": address("n"),
+                "recentBlockhashesSysvar": address("r"),
+                "nonceAuthority": address("a"),
+            })),
+            expected(SystemInstructionKind::AdvanceNonce, "n", "n", 0)
+        );
+        assert_eq!(parse("unknown", json!({"account": address("a")})), None);
+        assert_eq!(parse("transfer", json!({"source": address("a"), "lamports": 5})), None);
+        // Instructions with invalid addresses are skipped.
+        assert_eq!(parse("transfer", json!({"source": address("a"), "destination": "0OIl", "lamports": 5})), None);
+    }
+
+    #[test]
+    fn test_parse_token_instruction() {
+        let signature = "s".to_string();
+        let token_accounts = TokenAccounts::from([(address("a1"), (address("x"), 6))]);
+        let parse = |instruction_type: &str, info: Value| {
+            let context = InstructionContext {
+                signature: &signature,
+                instruction_index: 1,
+                token_accounts: &token_accounts,
+                account_keys: &[],
+            };
+            parse_token_instruction(&context, &token_instruction(instruction_type, info))
+        };
+        let expected = |kind, mint: &str, decimals, source: Option<&str>, destination: Option<&str>| {
+            Some(TokenTransfer {
+                signature: signature.clone(),
+                instruction_index: 1,
+                invocation: None,
+                kind,
+                mint: address(mint),
+                decimals,
+                source: source.map(address),
+                destination: destination.map(address),
+                amount: 5,
+            })
+        };
+        // Plain transfers take the mint and decimals from the token balances.
+        assert_eq!(
+            parse("transfer", json!({
+                "source": address("a1"),
+                "destination": address("b1"),
+                "authority": address("a"),
+                "amount": "5",
+            })),
+            expected(TokenTransferKind::Transfer, "x", 6, Some("a1"), Some("b1"))
+        );
+        assert_eq!(
+            parse("transfer", json!({
+                "source": address("c1"),
+                "destination": address("b1"),
+                "authority": address("c"),
+                "amount": "5",
+            })),
+            None
+        );
+        assert_eq!(
+            parse("transferChecked", json!({
+                "source": address("c1"),
+                "mint": address("y"),
+                "destination": address("b1"),
+                "authority": address("c"),
+                "tokenAmount": {"amount": "5", "decimals": 2, "uiAmount": 0.05, "uiAmountString": "0.05"},
+            })),
+            expected(TokenTransferKind::Transfer, "y", 2, Some("c1"), Some("b1"))
+        );
+        assert_eq!(
+            parse("mintTo", json!({
+                "mint": address("x"),
+                "account": address("a1"),
+                "mintAuthority": address("m"),
+                "amount": "5",
+            })),
+            expected(TokenTransferKind::MintTo, "x", 6, None, Some("a1"))
+        );
+        assert_eq!(
+            parse("burn", json!({
+                "account": address("a1"),
+                "mint": address("x"),
+                "authority": address("a"),
+                "amount": "5",
+            })),
+            expected(TokenTransferKind::Burn, "x", 6, Some("a1"), None)
+        );
+        assert_eq!(
+            parse("approve", json!({
+                "source": address("a1"),
+                "delegate": address("d"),
+                "owner": address("a"),
+                "amount": "5",
+            })),
+            None
+        );
+    }
+
+
+    #[test]
+    fn test_parse_raw_instruction() {
+        let signature = "s".to_string();
+        let token_accounts = TokenAccounts::new();
+        let account_keys = [address("a"), address("b"), address("p")];
+        let context = InstructionContext {
+            signature: &signature,
+            instruction_index: 3,
+            token_accounts: &token_accounts,
+            account_keys: &account_keys,
+        };
+        let expected = |accounts: &[&str]| {
+            Some(RawInstruction {
+                signature: signature.clone(),
+                instruction_index: 3,
+                invocation: None,
+                program_id: address("p"),
+                accounts: accounts.iter().map(|account| address(account)).collect(),
+                data: "3Bxs".to_string(),
+            })
+        };
+        let instruction = |value: Value| -> UiInstruction {serde_json::from_value(value).unwrap()};
+        let compiled = instruction(json!({"programIdIndex": 2, "accounts": [1, 0], "data": "3Bxs"}));
+        assert_eq!(parse_raw_instruction(&context, &compiled), expected(&["b", "a"]));
+        let decoded = instruction(json!({"programId": address("p"), "accounts": [address("a")], "data": "3Bxs"}));
+        assert_eq!(parse_raw_instruction(&context, &decoded), expected(&["a"]));
+        // Accounts outside of the account keys can't be resolved.
+        let unknown = instruction(json!({"programIdIndex": 2, "accounts": [3], "data": "3Bxs"}));
+        assert_eq!(parse_raw_instruction(&context, &unknown), None);
+        let invalid = instruction(json!({"programId": address("p"), "accounts": ["0OIl"], "data": "3Bxs"}));
+        assert_eq!(parse_raw_instruction(&context, &invalid), None);
+        let parsed = token_instruction(
+            "burn",
+            json!({"account": address("a"), "mint": address("m"), "authority": address("o"), "amount": "1"}),
+        );
+        assert_eq!(parse_raw_instruction(&context, &parsed), None);
+    }
+
+    #[test]
+    fn test_parse_stake_instruction() {
+        let signature = "s".to_string();
+        let token_accounts = TokenAccounts::new();
+        let context = InstructionContext {
+            signature: &signature,
+            instruction_index: 1,
+            token_accounts: &token_accounts,
+            account_keys: &[],
+        };
+        let parse = |instruction_type: &str, info: Value| {
+            let program_id = "Stake11111111111111111111111111111111111111";
+            let instruction = parsed_instruction("stake", program_id, instruction_type, info);
+            parse_stake_instruction(&context, &instruction)
+        };
+        let expected = |kind, stake_account: &str, vote_account: Option<&str>, destination: Option<&str>, amount| {
+            Some(StakeInstruction {
+                signature: signature.clone(),
+                instruction_index: 1,
+                invocation: None,
+                kind,
+                stake_account: address(stake_account),
+                vote_account: vote_account.map(address),
+                destination: destination.map(address),
+                amount,
+            })
+        };
+        assert_eq!(
+            parse("initialize", json!({"stakeAccount": address("s"), "authorized": {}, "lockup": {}})),
+            expected(StakeInstructionKind::Initialize, "s", None, None, 0)
+        );
+        assert_eq!(
+            parse("delegate", json!({
+                "stakeAccount": address("s"),
+                "voteAccount": address("v"),
+                "stakeAuthority": address("a"),
+            })),
+            expected(StakeInstructionKind::Delegate, "s", Some("v"), None, 0)
+        );
+        assert_eq!(
+            parse("deactivate", json!({"stakeAccount": address("s"), "stakeAuthority": address("a")})),
+            expected(StakeInstructionKind::Deactivate, "s", None, None, 0)
+        );
+        assert_eq!(
+            parse("withdraw", json!({"stakeAccount": address("s"), "destination": address("d"), "lamports": 5})),
+            expected(StakeInstructionKind::Withdraw, "s", None, Some("d"), 5)
+        );
+        assert_eq!(
+            parse("split", json!({"stakeAccount": address("s"), "newSplitAccount": address("n"), "lamports": 7})),
+            expected(StakeInstructionKind::Split, "s", None, Some("n"), 7)
+        );
+        assert_eq!(
+            parse("merge", json!({
+                "destination": address("d"),
+                "source": address("s"),
+                "stakeAuthority": address("a"),
+            })),
+            expected(StakeInstructionKind::Merge, "s", None, Some("d"), 0)
+        );
+        assert_eq!(parse("authorize", json!({"stakeAccount": address("s"), "authority": address("a")})), None);
+        assert_eq!(parse("delegate", json!({"stakeAccount": address("s")})), None);
+    }
+
+    #[test]
+    fn test_parse_vote_instruction() {
+        let signature = "s".to_string();
+        let token_accounts = TokenAccounts::new();
+        let context = InstructionContext {
+            signature: &signature,
+            instruction_index: 0,
+            token_accounts: &token_accounts,
+            account_keys: &[],
+        };
+        let parse = |instruction_type: &str, info: Value| {
+            let program_id = "Vote111111111111111111111111111111111111111";
+            let instruction = parsed_instruction("vote", program_id, instruction_type, info);
+            parse_vote_instruction(&context, &instruction)
+        };
+        let expected = |slot| {
+            Some(Vote {
+                signature: signature.clone(),
+                instruction_index: 0,
+                invocation: None,
+                vote_account: address("v"),
+                authority: address("a"),
+                slot,
+            })
+        };
+        let lockouts = json!({
+            "lockouts": [{"slot": 10, "confirmation_count": 2}, {"slot": 11, "confirmation_count": 1}],
+        });
+        assert_eq!(
+            parse("vote", json!({
+                "voteAccount": address("v"),
+                "voteAuthority": address("a"),
+                "vote": {"slots": [8, 9]},
+            })),
+            expected(Some(9))
+        );
+        assert_eq!(
+            parse(
+                "compactupdatevotestate",
+                json!({"voteAccount": address("v"), "voteAuthority": address("a"), "voteStateUpdate": lockouts}),
+            ),
+            expected(Some(11))
+        );
+        assert_eq!(
+            parse("towersync", json!({
+                "voteAccount": address("v"),
+                "voteAuthority": address("a"),
+                "towerSync": lockouts,
+            })),
+            expected(Some(11))
+        );
+        assert_eq!(
+            parse("vote", json!({"voteAccount": address("v"), "voteAuthority": address("a"), "vote": {"slots": []}})),
+            expected(None)
+        );
+        assert_eq!(
+            parse("withdraw", json!({"voteAccount": address("v"), "destination": address("d"), "lamports": 5})),
+            None
+        );
+    }
+
+    struct CustomParser;
+
+    impl InstructionParser for CustomParser {
+        fn name(&self) -> &str {
+            "test-custom"
+        }
+
+        fn enabled_by_default(&self) -> bool {
+            false
+        }
+
+        fn parse(&self, context: &InstructionContext, instruction: &UiInstruction) -> Option<InstructionEvent> {
+            let UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed_instruction)) = instruction else {
+                return None;
+            };
+            (parsed_instruction.program == "custom").then(|| InstructionEvent::Transfer(Transaction {
+                signature: context.signature.clone(),
+                instruction_index: context.instruction_index,
+                invocation: None,
+                kind: SystemInstructionKind::Transfer,
+                sender: "custom".to_string(),
+                receiver: "custom".to_string(),
+                amount: 0,
+            }))
+        }
+    }
+
+    #[test]
+    fn test_parse_memo() {
+        let signature = "s".to_string();
+        let token_accounts = TokenAccounts::new();
+        let context = InstructionContext {
+            signature: &signature,
+            instruction_index: 0,
+            token_accounts: &token_accounts,
+            account_keys: &[],
+        };
+        let memo = |program_id: &str, parsed: Value| -> UiInstruction {
+            serde_json::from_value(json!({
+                "program": "spl-memo",
+                "programId": program_id,
+                "parsed": parsed,
+                "stackHeight": null,
+            })).unwrap()
+        };
+        // Both memo program versions are parsed into the same program name.
+        for program_id in [
+            "Memo1UhkJRfHyvLMcVucJwxXeuD728EVVhy3Dmg6VFpi",
+            "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr",
+        ] {
+            assert_eq!(
+                MemoParser.parse(&context, &memo(program_id, json!("order-1"))),
+                Some(InstructionEvent::Memo("order-1".to_string()))
+            );
+        }
+        assert_eq!(MemoParser.parse(&context, &token_instruction("burn", json!({}))), None);
+    }
+
+    #[test]
+    fn test_registry() {
+        // A registry of its own keeps the global one untouched for the tests running in parallel.
+        let mut registry = ParserRegistry::default();
+        assert_eq!(
+            registry.parsers(),
+            ["system", "spl-token", "spl-memo", "stake", "vote"].map(|name| (name.to_string(), true))
+        );
+        assert!(matches!(registry.set_enabled("unknown", true), Err(Error::UnknownParser(name)) if name == "unknown"));
+
+        let signature = "s".to_string();
+        let token_accounts = TokenAccounts::new();
+        let context = InstructionContext {
+            signature: &signature,
+            instruction_index: 0,
+            token_accounts: &token_accounts,
+            account_keys: &[],
+        };
+        let custom = parsed_instruction("custom", "c", "transfer", json!({}));
+        // Parsers registered from outside start with their default and can be toggled.
+        registry.register(CustomParser);
+        assert!(registry.parsers().contains(&("test-custom".to_string(), false)));
+        assert_eq!(parse_instruction(&registry.enabled_parsers(), &context, &custom), None);
+        registry.set_enabled("test-custom", true).unwrap();
+        let event = parse_instruction(&registry.enabled_parsers(), &context, &custom);
+        assert!(matches!(event, Some(InstructionEvent::Transfer(transfer)) if transfer.sender == "custom"));
+        registry.set_enabled("test-custom", false).unwrap();
+        assert_eq!(parse_instruction(&registry.enabled_parsers(), &context, &custom), None);
+        // The global registry is not affected.
+        assert!(!parsers().iter().any(|(name, _)| name == "test-custom"));
+    }
+}
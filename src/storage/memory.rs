@@ -1,7 +1,18 @@
 use crate::error::{Error, Result};
-use crate::storage::Storage;
-use crate::types::{Account, Address, Block, TransactionWithMeta};
-use std::collections::HashMap;
+use chrono::NaiveDate;
+use solana_sdk::clock::Slot;
+use solana_sdk::pubkey::Pubkey;
+use crate::storage::{
+    AccountingMode, MemoFilter, Order, Page, Paginated, Storage, TransactionCursor, TransactionFilter,
+};
+use crate::types::{
+    parse_address, Account, AccountSummary, Address, Block, BlockReward, CustomInstruction, RawInstruction, Reward,
+    Signature, StakeAccount, StakeInstruction, StakeInstructionKind, TokenBalance, TokenBalanceChange, TokenTransfer,
+    TransactionWithMeta, Validator,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
+use std::ops::Bound;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -11,17 +22,425 @@ struct TransactionIndex {
     pub index: usize,
 }
 
+/// Removes the entries of the block with `block_height` from `entries`. Returns `true` if there is
+/// no entry left.
+fn remove_entries(entries: &mut Vec<TransactionIndex>, block_height: u64) -> bool {
+    // Entries are added in ascending block order, so the ones of the block are at the end.
+    while entries.last().is_some_and(|entry| entry.block_height == block_height) {
+        entries.pop();
+    }
+    entries.is_empty()
+}
+
+impl TransactionIndex {
+    fn cursor(&self) -> TransactionCursor {
+        TransactionCursor {block_height: self.block_height, index: self.index}
+    }
+}
+
+/// Removes the entries of the block with `block_height` from the index of `key`, and the whole
+/// entry if it becomes empty. Returns `true` if there is no entry left for `key`.
+fn remove_from_index<Key: Hash + Eq>(
+    index: &mut HashMap<Key, Vec<TransactionIndex>>,
+    key: &Key,
+    block_height: u64,
+) -> bool {
+    let Some(entries) = index.get_mut(key) else {
+        return true;
+    };
+    if remove_entries(entries, block_height) {
+        index.remove(key);
+        return true;
+    }
+    false
+}
+
+/// The key of an address of a block, which `add_block` made sure is a valid public key.
+fn key(address: &Address) -> Pubkey {
+    parse_address(address).unwrap()
+}
+
+/// The address of a key, to hand the stored data out of the storage.
+fn to_address(key: &Pubkey) -> Address {
+    key.to_string()
+}
+
+/// Adds the signed `lamports` to `balance`.
+fn credit(address: &Pubkey, balance: u64, lamports: i64) -> u64 {
+    match u64::try_from(lamports) {
+        Ok(lamports) => balance + lamports,
+        Err(_) => debit(address, balance, lamports.unsigned_abs()),
+    }
+}
+
+/// Subtracts `amount` from `balance`, which can only go below zero if the stored balance is off.
+fn debit(address: &Pubkey, balance: u64, amount: u64) -> u64 {
+    balance.checked_sub(amount).unwrap_or_else(|| {
+        log::warn!("Balance of {} below zero: {} - {}", address, balance, amount);
+        0
+    })
+}
+
+#[derive(Clone, Debug)]
+struct TokenAccount {
+    owner: Pubkey,
+    mint: Pubkey,
+    decimals: u8,
+    amount: u64,
+}
+
+/// The running totals of an account, kept up to date with every added and removed block.
+#[derive(Default, Debug)]
+struct AccountStats {
+    total_sent: u64,
+    total_received: u64,
+    /// The number of transactions with each counterparty.
+    counterparties: HashMap<Pubkey, usize>,
+}
+
+impl AccountStats {
+    fn is_empty(&self) -> bool {
+        self.total_sent == 0 && self.total_received == 0 && self.counterparties.is_empty()
+    }
+}
+
+/// Addresses are kept as 32 byte public keys rather than base58 strings to save memory, in the indexes as well as in
+/// the blocks, and only converted back when they leave the storage.
 #[derive(Default, Debug)]
 struct Data {
     last_block: u64,
-    blocks: HashMap<u64, Block>,
-    accounts: HashMap<Address, i64>,
-    transaction_index: HashMap<Address, Vec<TransactionIndex>>,
+    blocks: HashMap<u64, Block<Pubkey>>,
+    accounts: BTreeMap<Pubkey, u64>,
+    transaction_index: HashMap<Pubkey, Vec<TransactionIndex>>,
+    account_stats: HashMap<Pubkey, AccountStats>,
+    signature_index: HashMap<Signature, Vec<TransactionIndex>>,
+    day_index: HashMap<NaiveDate, Vec<TransactionIndex>>,
+    /// Ordered to find the memos with a prefix without a full scan.
+    memo_index: BTreeMap<String, Vec<TransactionIndex>>,
+    token_accounts: HashMap<Pubkey, TokenAccount>,
+    owner_index: HashMap<Pubkey, HashSet<Pubkey>>,
+    mint_index: HashMap<Pubkey, Vec<TransactionIndex>>,
+    program_index: HashMap<Pubkey, Vec<TransactionIndex>>,
+    custom_index: HashMap<Pubkey, Vec<TransactionIndex>>,
+    stake_index: HashMap<Pubkey, Vec<TransactionIndex>>,
+    delegation_index: HashMap<Pubkey, Vec<TransactionIndex>>,
+    vote_index: HashMap<Pubkey, Vec<TransactionIndex>>,
+    reward_index: HashMap<Pubkey, Vec<TransactionIndex>>,
+}
+
+impl Data {
+    fn collect_transactions(&self, indexes: &[TransactionIndex]) -> Vec<TransactionWithMeta> {
+        let mut transactions = Vec::with_capacity(indexes.len());
+        for index in indexes {
+            let block = self.blocks.get(&index.block_height).unwrap();
+            let transaction = block.transactions.get(index.index).unwrap();
+            transactions.push(
+                TransactionWithMeta {
+                    data: transaction.map_addresses(&to_address),
+                    timestamp: block.timestamp,
+                    meta: block.transaction_meta(&transaction.signature).map(|meta| meta.map_addresses(&to_address)),
+                }
+            );
+        }
+        transactions
+    }
+
+    fn collect_token_transfers(&self, indexes: &[TransactionIndex]) -> Vec<TransactionWithMeta<TokenTransfer>> {
+        let mut transfers = Vec::with_capacity(indexes.len());
+        for index in indexes {
+            let block = self.blocks.get(&index.block_height).unwrap();
+            let transfer = block.token_transfers.get(index.index).unwrap();
+            transfers.push(
+                TransactionWithMeta {
+                    data: transfer.map_addresses(&to_address),
+                    timestamp: block.timestamp,
+                    meta: block.transaction_meta(&transfer.signature).map(|meta| meta.map_addresses(&to_address)),
+                }
+            );
+        }
+        transfers
+    }
+
+    fn collect_raw_instructions(&self, indexes: &[TransactionIndex]) -> Vec<TransactionWithMeta<RawInstruction>> {
+        let mut instructions = Vec::with_capacity(indexes.len());
+        for index in indexes {
+            let block = self.blocks.get(&index.block_height).unwrap();
+            let instruction = block.raw_instructions.get(index.index).unwrap();
+            instructions.push(
+                TransactionWithMeta {
+                    data: instruction.map_addresses(&to_address),
+                    timestamp: block.timestamp,
+                    meta: block.transaction_meta(&instruction.signature).map(|meta| meta.map_addresses(&to_address)),
+                }
+            );
+        }
+        instructions
+    }
+
+    fn collect_custom_instructions(
+        &self,
+        indexes: &[TransactionIndex],
+    ) -> Vec<TransactionWithMeta<CustomInstruction>> {
+        let mut instructions = Vec::with_capacity(indexes.len());
+        for index in indexes {
+            let block = self.blocks.get(&index.block_height).unwrap();
+            let instruction = block.custom_instructions.get(index.index).unwrap();
+            instructions.push(
+                TransactionWithMeta {
+                    data: instruction.map_addresses(&to_address),
+                    timestamp: block.timestamp,
+                    meta: block.transaction_meta(&instruction.signature).map(|meta| meta.map_addresses(&to_address)),
+                }
+            );
+        }
+        instructions
+    }
+
+    fn collect_stake_instructions(&self, indexes: &[TransactionIndex]) -> Vec<TransactionWithMeta<StakeInstruction>> {
+        let mut instructions = Vec::with_capacity(indexes.len());
+        for index in indexes {
+            let block = self.blocks.get(&index.block_height).unwrap();
+            let instruction = block.stake_instructions.get(index.index).unwrap();
+            instructions.push(
+                TransactionWithMeta {
+                    data: instruction.map_addresses(&to_address),
+                    timestamp: block.timestamp,
+                    meta: block.transaction_meta(&instruction.signature).map(|meta| meta.map_addresses(&to_address)),
+                }
+            );
+        }
+        instructions
+    }
+
+    fn stake_account(&self, key: &Pubkey) -> Option<StakeAccount> {
+        let stake_index = self.stake_index.get(key)?;
+        Some(StakeAccount::new(
+            to_address(key),
+            self.accounts.get(key).copied(),
+            self.collect_stake_instructions(stake_index),
+        ))
+    }
+
+    /// Sets the token account of `change` to `amount`, or removes it if it doesn't exist.
+    fn set_token_account(&mut self, change: &TokenBalanceChange<Pubkey>, amount: Option<u64>) {
+        let account = change.account;
+        if let Some(previous) = self.token_accounts.remove(&account) {
+            if let Some(accounts) = self.owner_index.get_mut(&previous.owner) {
+                accounts.remove(&account);
+                if accounts.is_empty() {
+                    self.owner_index.remove(&previous.owner);
+                }
+            }
+        }
+        if let Some(amount) = amount {
+            let owner = change.owner;
+            self.token_accounts.insert(
+                account,
+                TokenAccount {
+                    owner,
+                    mint: change.mint,
+                    decimals: change.decimals,
+                    amount,
+                }
+            );
+            self.owner_index.entry(owner).or_default().insert(account);
+        }
+    }
+
+    /// Applies the successful transactions of `block` to the account balances. New accounts start
+    /// with their balance before the block.
+    fn apply_transfers(&mut self, block: &Block<Pubkey>) {
+        let pre_balances = block.pre_balances();
+        for (sender, receiver, amount) in block.lamport_movements() {
+            let receiver_account = self.accounts
+                .entry(*receiver)
+                .or_insert_with(|| pre_balances.get(receiver).copied().unwrap_or_default());
+            *receiver_account += amount;
+            let sender_account = self.accounts
+                .entry(*sender)
+                .or_insert_with(|| pre_balances.get(sender).copied().unwrap_or_default());
+            *sender_account = debit(sender, *sender_account, amount);
+        }
+        for reward in &block.rewards {
+            let account = self.accounts
+                .entry(reward.address)
+                .or_insert_with(|| pre_balances.get(&reward.address).copied().unwrap_or_default());
+            *account = credit(&reward.address, *account, reward.lamports);
+        }
+    }
+
+    fn add_account_stats(&mut self, block: &Block<Pubkey>) {
+        for transaction in &block.transactions {
+            if transaction.sender == transaction.receiver {
+                continue;
+            }
+            for (address, counterparty) in [
+                (&transaction.sender, &transaction.receiver),
+                (&transaction.receiver, &transaction.sender),
+            ] {
+                let stats = self.account_stats.entry(*address).or_default();
+                *stats.counterparties.entry(*counterparty).or_default() += 1;
+            }
+        }
+        for transaction in block.successful_transactions() {
+            self.account_stats.entry(transaction.sender).or_default().total_sent += transaction.amount;
+            self.account_stats.entry(transaction.receiver).or_default().total_received += transaction.amount;
+        }
+    }
+
+    fn remove_account_stats(&mut self, block: &Block<Pubkey>) {
+        for transaction in block.successful_transactions() {
+            if let Some(stats) = self.account_stats.get_mut(&transaction.sender) {
+                stats.total_sent = stats.total_sent.saturating_sub(transaction.amount);
+            }
+            if let Some(stats) = self.account_stats.get_mut(&transaction.receiver) {
+                stats.total_received = stats.total_received.saturating_sub(transaction.amount);
+            }
+        }
+        for transaction in &block.transactions {
+            if transaction.sender == transaction.receiver {
+                continue;
+            }
+            for (address, counterparty) in [
+                (&transaction.sender, &transaction.receiver),
+                (&transaction.receiver, &transaction.sender),
+            ] {
+                let Some(stats) = self.account_stats.get_mut(address) else {
+                    continue;
+                };
+                if let Some(count) = stats.counterparties.get_mut(counterparty) {
+                    *count -= 1;
+                    if *count == 0 {
+                        stats.counterparties.remove(counterparty);
+                    }
+                }
+            }
+        }
+        for transaction in &block.transactions {
+            for address in [&transaction.sender, &transaction.receiver] {
+                if self.account_stats.get(address).is_some_and(AccountStats::is_empty) {
+                    self.account_stats.remove(address);
+                }
+            }
+        }
+    }
+
+    /// Sets the account balances to the ones after `block`.
+    fn apply_balance_changes(&mut self, block: &Block<Pubkey>) {
+        for change in &block.balance_changes {
+            self.accounts.insert(change.address, change.post_balance);
+        }
+    }
+
+    /// Reverts everything the block with `height` applied in `add_block` and removes it.
+    fn remove_block(&mut self, height: u64, accounting: AccountingMode) {
+        let Some(block) = self.blocks.remove(&height) else {
+            return;
+        };
+        self.remove_account_stats(&block);
+        match accounting {
+            AccountingMode::Transfers => {
+                for reward in block.rewards.iter().rev() {
+                    if let Some(account) = self.accounts.get_mut(&reward.address) {
+                        *account = credit(&reward.address, *account, -reward.lamports);
+                    }
+                }
+                for (sender, receiver, amount) in block.lamport_movements().rev() {
+                    if let Some(receiver_account) = self.accounts.get_mut(receiver) {
+                        *receiver_account = debit(receiver, *receiver_account, amount);
+                    }
+                    if let Some(sender_account) = self.accounts.get_mut(sender) {
+                        *sender_account += amount;
+                    }
+                }
+            }
+            AccountingMode::Balances => {
+                for change in &block.balance_changes {
+                    self.accounts.insert(change.address, change.pre_balance);
+                }
+            }
+        }
+        for change in block.token_balance_changes.iter().rev() {
+            self.set_token_account(change, change.pre_amount);
+        }
+        for transfer in block.token_transfers.iter().rev() {
+            remove_from_index(&mut self.mint_index, &transfer.mint, height);
+        }
+        for instruction in block.raw_instructions.iter().rev() {
+            remove_from_index(&mut self.program_index, &instruction.program_id, height);
+        }
+        for instruction in block.custom_instructions.iter().rev() {
+            remove_from_index(&mut self.custom_index, &instruction.program_id, height);
+        }
+        for vote in block.votes.iter().rev() {
+            remove_from_index(&mut self.vote_index, &vote.vote_account, height);
+        }
+        // Accounts are only tracked in the transfers mode as long as any transaction, stake
+        // instruction or reward involves them.
+        let mut removed_addresses: HashSet<Pubkey> = HashSet::new();
+        for reward in block.rewards.iter().rev() {
+            if remove_from_index(&mut self.reward_index, &reward.address, height) {
+                removed_addresses.insert(reward.address);
+            }
+        }
+        for instruction in block.stake_instructions.iter().rev() {
+            for address in [Some(&instruction.stake_account), instruction.destination.as_ref()].into_iter().flatten() {
+                if remove_from_index(&mut self.stake_index, address, height) {
+                    removed_addresses.insert(*address);
+                }
+            }
+            if let Some(vote_account) = &instruction.vote_account {
+                remove_from_index(&mut self.delegation_index, vote_account, height);
+            }
+        }
+        let day = block.day();
+        let memos = block.memos();
+        for transaction in block.transactions.iter().rev() {
+            for address in [transaction.sender, transaction.receiver] {
+                if remove_from_index(&mut self.transaction_index, &address, height) {
+                    removed_addresses.insert(address);
+                }
+            }
+            remove_from_index(&mut self.signature_index, &transaction.signature, height);
+            if let Some(memo) = memos.get(&transaction.signature) {
+                if self.memo_index.get_mut(*memo).is_some_and(|entries| remove_entries(entries, height)) {
+                    self.memo_index.remove(*memo);
+                }
+            }
+            if let Some(day) = day {
+                remove_from_index(&mut self.day_index, &day, height);
+            }
+        }
+        if accounting == AccountingMode::Transfers {
+            for address in removed_addresses {
+                if !self.transaction_index.contains_key(&address)
+                    && !self.stake_index.contains_key(&address)
+                    && !self.reward_index.contains_key(&address) {
+                    self.accounts.remove(&address);
+                }
+            }
+        }
+        if accounting == AccountingMode::Balances {
+            // Accounts without balance didn't exist before the block.
+            for change in &block.balance_changes {
+                if change.pre_balance == 0 {
+                    self.accounts.remove(&change.address);
+                }
+            }
+        }
+    }
 }
 
 #[derive(Default, Clone, Debug)]
 pub struct Memory {
-    data: Arc<Mutex<Data>>
+    data: Arc<Mutex<Data>>,
+    accounting: AccountingMode,
+}
+
+impl Memory {
+    pub fn new(accounting: AccountingMode) -> Self {
+        Self {data: Arc::default(), accounting}
+    }
 }
 
 impl Storage for Memory {
@@ -37,7 +456,15 @@ impl Storage for Memory {
                 format!("Block height must be ascending. last_block: {}", data.last_block))
             );
         }
+        let day = block.day().ok_or_else(|| {
+            Error::InvalidBlock(block.height, format!("Invalid timestamp: {}", block.timestamp))
+        })?;
+        if let Some(address) = block.invalid_address() {
+            return Err(Error::InvalidBlock(block.height, format!("Invalid address: {}", address)));
+        }
+        let block = block.map_addresses(&key);
         data.last_block = block_height;
+        let memos = block.memos();
         for (index, transaction) in block.transactions.iter().enumerate() {
             // Update transaction index
             let tx_index = TransactionIndex {
@@ -45,200 +472,446 @@ impl Storage for Memory {
                 index,
             };
             let sender_index = data.transaction_index
-                .entry(transaction.sender.clone()).or_default();
+                .entry(transaction.sender).or_default();
             if !sender_index.contains(&tx_index) {
                 sender_index.push(tx_index.clone());
             }
             let receiver_index = data.transaction_index
-                .entry(transaction.receiver.clone()).or_default();
+                .entry(transaction.receiver).or_default();
             if !receiver_index.contains(&tx_index) {
                 receiver_index.push(tx_index.clone());
             }
-            // Update accounts
-            let mut receiver_account = data.accounts
-                .entry(transaction.receiver.clone()).or_default();
-            *receiver_account += transaction.amount as i64;
-            let mut sender_account = data.accounts
-                .entry(transaction.sender.clone()).or_default();
-            *sender_account -= transaction.amount as i64;
+            data.signature_index
+                .entry(transaction.signature.clone()).or_default()
+                .push(tx_index.clone());
+            if let Some(memo) = memos.get(&transaction.signature) {
+                data.memo_index.entry((*memo).clone()).or_default().push(tx_index.clone());
+            }
+            data.day_index.entry(day).or_default().push(tx_index);
+        }
+        for (index, transfer) in block.token_transfers.iter().enumerate() {
+            data.mint_index
+                .entry(transfer.mint).or_default()
+                .push(TransactionIndex {block_height, index});
+        }
+        for (index, instruction) in block.raw_instructions.iter().enumerate() {
+            data.program_index
+                .entry(instruction.program_id).or_default()
+                .push(TransactionIndex {block_height, index});
+        }
+        for (index, instruction) in block.custom_instructions.iter().enumerate() {
+            data.custom_index
+                .entry(instruction.program_id).or_default()
+                .push(TransactionIndex {block_height, index});
+        }
+        for (index, instruction) in block.stake_instructions.iter().enumerate() {
+            let stake_index = TransactionIndex {block_height, index};
+            for address in [Some(&instruction.stake_account), instruction.destination.as_ref()].into_iter().flatten() {
+                let address_index = data.stake_index.entry(*address).or_default();
+                if !address_index.contains(&stake_index) {
+                    address_index.push(stake_index.clone());
+                }
+            }
+            if instruction.kind == StakeInstructionKind::Delegate {
+                if let Some(vote_account) = &instruction.vote_account {
+                    data.delegation_index.entry(*vote_account).or_default().push(stake_index);
+                }
+            }
+        }
+        for (index, vote) in block.votes.iter().enumerate() {
+            data.vote_index
+                .entry(vote.vote_account).or_default()
+                .push(TransactionIndex {block_height, index});
+        }
+        for (index, reward) in block.rewards.iter().enumerate() {
+            data.reward_index
+                .entry(reward.address).or_default()
+                .push(TransactionIndex {block_height, index});
+        }
+        data.add_account_stats(&block);
+        for change in &block.token_balance_changes {
+            data.set_token_account(change, change.post_amount);
+        }
+        // Update accounts
+        match self.accounting {
+            AccountingMode::Transfers => data.apply_transfers(&block),
+            AccountingMode::Balances => data.apply_balance_changes(&block),
         }
         data.blocks.insert(block_height, block);
         Ok(())
     }
 
-    async fn get_accounts(&self) -> Result<Vec<Account>> {
+    async fn get_accounts(&self, page: &Page<Address>) -> Result<Paginated<Account, Address>> {
         let data = self.data.lock().await;
-        let mut accounts = Vec::with_capacity(data.accounts.len());
-        for (address, balance) in data.accounts.iter() {
-            accounts.push(
-                Account {
-                    address: address.clone(),
-                    balance: balance.clone(),
-                }
-            );
+        // The accounts are ordered by their keys, so there is nothing after invalid cursors.
+        let cursor = match page.cursor.as_deref().map(parse_address) {
+            Some(Ok(cursor)) => Some(cursor),
+            Some(Err(_)) => return Ok(Paginated {items: Vec::new(), next_cursor: None}),
+            None => None,
+        };
+        let bounds = match (page.order, &cursor) {
+            (_, None) => (Bound::Unbounded, Bound::Unbounded),
+            (Order::Asc, Some(cursor)) => (Bound::Excluded(cursor), Bound::Unbounded),
+            (Order::Desc, Some(cursor)) => (Bound::Unbounded, Bound::Excluded(cursor)),
+        };
+        let accounts = data.accounts.range::<Pubkey, _>(bounds).map(|(address, balance)| {
+            Account {
+                address: address.to_string(),
+                balance: *balance,
+            }
+        });
+        let address = |account: &Account| account.address.clone();
+        Ok(match page.order {
+            Order::Asc => Paginated::collect(accounts, page.limit, address),
+            Order::Desc => Paginated::collect(accounts.rev(), page.limit, address),
+        })
+    }
+
+    async fn get_account(&self, address: &Address) -> Result<Option<AccountSummary>> {
+        let data = self.data.lock().await;
+        let Ok(address_key) = parse_address(address) else {
+            return Ok(None);
+        };
+        let balance = data.accounts.get(&address_key).copied();
+        let transactions = data.transaction_index.get(&address_key);
+        if balance.is_none() && transactions.is_none() {
+            return Ok(None);
         }
-        Ok(accounts)
+        let stats = data.account_stats.get(&address_key);
+        Ok(Some(
+            AccountSummary {
+                address: address.clone(),
+                balance,
+                first_seen_block: transactions.and_then(|indexes| indexes.first()).map(|index| index.block_height),
+                last_seen_block: transactions.and_then(|indexes| indexes.last()).map(|index| index.block_height),
+                total_sent: stats.map_or(0, |stats| stats.total_sent),
+                total_received: stats.map_or(0, |stats| stats.total_received),
+                transaction_count: transactions.map_or(0, Vec::len),
+                counterparties: stats.map_or(0, |stats| stats.counterparties.len()),
+            }
+        ))
     }
 
-    async fn get_transactions(&self, address: &Address) -> Result<Vec<TransactionWithMeta>> {
+    async fn get_transactions(
+        &self,
+        address: &Address,
+        filter: &TransactionFilter,
+        page: &Page<TransactionCursor>,
+    ) -> Result<Paginated<TransactionWithMeta, TransactionCursor>> {
         let data = self.data.lock().await;
-        let mut transactions = Vec::new();
-        if let Some(transaction_index) = data.transaction_index.get(address) {
-            transactions.reserve(transaction_index.len());
-            for index in transaction_index {
-                let block = data.blocks.get(&index.block_height).unwrap();
-                let transaction = block.transactions.get(index.index).unwrap();
-                transactions.push(
-                    TransactionWithMeta {
-                        data: transaction.clone(),
-                        timestamp: block.timestamp,
-                    }
-                );
+        let Ok(address) = parse_address(address) else {
+            return Ok(Paginated {items: Vec::new(), next_cursor: None});
+        };
+        let transaction_index = data.transaction_index.get(&address).map(Vec::as_slice).unwrap_or_default();
+        // The index is ordered by block, so the cursor can be searched. The filter is checked per transaction, block
+        // timestamps are not guaranteed to increase with the height.
+        let matches = |index: &&TransactionIndex| {
+            let block = &data.blocks[&index.block_height];
+            let transaction = &block.transactions[index.index];
+            let meta = block.transaction_meta(&transaction.signature);
+            filter.matches_transaction(&address, block.timestamp, transaction, meta)
+        };
+        let indexes = match page.order {
+            Order::Asc => {
+                let start = page.cursor.map_or(0, |cursor| {
+                    transaction_index.partition_point(|index| index.cursor() <= cursor)
+                });
+                let indexes = transaction_index[start..].iter().filter(matches);
+                Paginated::collect(indexes, page.limit, |index| index.cursor())
+            }
+            Order::Desc => {
+                let end = page.cursor.map_or(transaction_index.len(), |cursor| {
+                    transaction_index.partition_point(|index| index.cursor() < cursor)
+                });
+                let indexes = transaction_index[..end].iter().rev().filter(matches);
+                Paginated::collect(indexes, page.limit, |index| index.cursor())
             }
+        };
+        let items: Vec<TransactionIndex> = indexes.items.into_iter().cloned().collect();
+        Ok(Paginated {items: data.collect_transactions(&items), next_cursor: indexes.next_cursor})
+    }
+
+    async fn get_transactions_by_signature(&self, signature: &Signature) -> Result<Vec<TransactionWithMeta>> {
+        let data = self.data.lock().await;
+        match data.signature_index.get(signature) {
+            Some(signature_index) => Ok(data.collect_transactions(signature_index)),
+            None => Ok(Vec::new()),
         }
-        Ok(transactions)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::types::Transaction;
-    use super::*;
+    async fn get_transactions_by_day(&self, day: &NaiveDate) -> Result<Vec<TransactionWithMeta>> {
+        let data = self.data.lock().await;
+        match data.day_index.get(day) {
+            Some(day_index) => Ok(data.collect_transactions(day_index)),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn get_transactions_by_memo(&self, filter: &MemoFilter) -> Result<Vec<TransactionWithMeta>> {
+        let data = self.data.lock().await;
+        let mut indexes: Vec<TransactionIndex> = match filter {
+            MemoFilter::Exact(memo) => data.memo_index.get(memo).cloned().unwrap_or_default(),
+            MemoFilter::Prefix(prefix) => data.memo_index
+                .range::<str, _>((Bound::Included(prefix.as_str()), Bound::Unbounded))
+                .take_while(|(memo, _)| memo.starts_with(prefix.as_str()))
+                .flat_map(|(_, indexes)| indexes.iter().cloned())
+                .collect(),
+        };
+        indexes.sort_unstable_by_key(|index| (index.block_height, index.index));
+        Ok(data.collect_transactions(&indexes))
+    }
 
-    async fn assert_accounts(memory: &Memory, expected_accounts: &Vec<Account>) {
-        let accounts = memory.get_accounts().await.unwrap();
-        assert_eq!(accounts.len(), expected_accounts.len());
-        for account in expected_accounts.clone() {
-            assert!(accounts.contains(&account));
+    async fn get_token_balances(&self, owner: &Address) -> Result<Vec<TokenBalance>> {
+        let data = self.data.lock().await;
+        let mut balances: BTreeMap<Address, TokenBalance> = BTreeMap::new();
+        let token_accounts = parse_address(owner).ok().and_then(|owner| data.owner_index.get(&owner));
+        for address in token_accounts.into_iter().flatten() {
+            let account = data.token_accounts.get(address).unwrap();
+            let mint = account.mint.to_string();
+            let balance = balances.entry(mint.clone()).or_insert_with(|| TokenBalance {
+                mint,
+                decimals: account.decimals,
+                amount: 0,
+            });
+            balance.amount = balance.amount.saturating_add(account.amount);
         }
+        Ok(balances.into_values().collect())
     }
-    async fn assert_transactions(
-        memory: &Memory,
-        address: &Address,
-        expected_transactions: Vec<TransactionWithMeta>
-    ) {
-        let transactions = memory.get_transactions(&address).await.unwrap();
-        assert_eq!(transactions.len(), expected_transactions.len());
-        for transaction in expected_transactions.clone() {
-            assert!(transactions.contains(&transaction));
+
+    async fn get_token_transfers_by_mint(&self, mint: &Address) -> Result<Vec<TransactionWithMeta<TokenTransfer>>> {
+        let data = self.data.lock().await;
+        match parse_address(mint).ok().and_then(|mint| data.mint_index.get(&mint)) {
+            Some(mint_index) => Ok(data.collect_token_transfers(mint_index)),
+            None => Ok(Vec::new()),
         }
     }
 
-    fn get_block(height: u64, transactions: Vec<Transaction>) -> Block {
-        Block {
-            height,
-            hash: height.to_string(),
-            timestamp: height as i64,
-            transactions,
+    async fn get_instructions_by_program(
+        &self,
+        program_id: &Address,
+    ) -> Result<Vec<TransactionWithMeta<RawInstruction>>> {
+        let data = self.data.lock().await;
+        match parse_address(program_id).ok().and_then(|program_id| data.program_index.get(&program_id)) {
+            Some(program_index) => Ok(data.collect_raw_instructions(program_index)),
+            None => Ok(Vec::new()),
         }
     }
 
-    #[tokio::test]
-    async fn test_add_and_fetch_data() {
-        let mut memory = Memory::default();
+    async fn get_custom_instructions_by_program(
+        &self,
+        program_id: &Address,
+    ) -> Result<Vec<TransactionWithMeta<CustomInstruction>>> {
+        let data = self.data.lock().await;
+        match parse_address(program_id).ok().and_then(|program_id| data.custom_index.get(&program_id)) {
+            Some(custom_index) => Ok(data.collect_custom_instructions(custom_index)),
+            None => Ok(Vec::new()),
+        }
+    }
 
-        assert_eq!(memory.get_accounts().await.unwrap().len(), 0);
+    async fn get_stake_account(&self, address: &Address) -> Result<Option<StakeAccount>> {
+        let data = self.data.lock().await;
+        Ok(parse_address(address).ok().and_then(|address| data.stake_account(&address)))
+    }
 
-        let mut account_0 = Account {
-            address: "0".to_string(),
-            balance: 0,
+    async fn get_validator(&self, vote_account: &Address) -> Result<Option<Validator>> {
+        let data = self.data.lock().await;
+        let Ok(vote_key) = parse_address(vote_account) else {
+            return Ok(None);
         };
-        let mut account_1 = Account {
-            address: "1".to_string(),
-            balance: 0,
+        let mut validator = Validator {
+            vote_account: vote_account.clone(),
+            votes: 0,
+            last_voted_slot: None,
+            last_vote_timestamp: None,
+            stake_accounts: Vec::new(),
         };
+        for index in data.vote_index.get(&vote_key).into_iter().flatten() {
+            let block = data.blocks.get(&index.block_height).unwrap();
+            let vote = block.votes.get(index.index).unwrap();
+            let meta = block.transaction_meta(&vote.signature);
+            if meta.is_some_and(|meta| !meta.status.is_success()) {
+                continue;
+            }
+            validator.votes += 1;
+            validator.last_voted_slot = validator.last_voted_slot.max(vote.slot);
+            validator.last_vote_timestamp = validator.last_vote_timestamp.max(Some(block.timestamp));
+        }
+        let mut stake_accounts: BTreeSet<Address> = BTreeSet::new();
+        for index in data.delegation_index.get(&vote_key).into_iter().flatten() {
+            let block = data.blocks.get(&index.block_height).unwrap();
+            stake_accounts.insert(to_address(&block.stake_instructions.get(index.index).unwrap().stake_account));
+        }
+        for address in stake_accounts {
+            let stake_account = data.stake_account(&key(&address)).unwrap();
+            if stake_account.vote_account.as_ref() == Some(vote_account) && !stake_account.deactivated {
+                validator.stake_accounts.push(stake_account.address);
+            }
+        }
+        if validator.votes == 0 && validator.stake_accounts.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(validator))
+    }
 
-        let tx_0 = TransactionWithMeta {
-            data: Transaction {
-                sender: account_0.address.clone(),
-                receiver: account_1.address.clone(),
-                amount: 1,
-            },
-            timestamp: 0,
-        };
-        let tx_1 = TransactionWithMeta {
-            data: Transaction {
-                sender: account_1.address.clone(),
-                receiver: account_0.address.clone(),
-                amount: 2,
-            },
-            timestamp: 1,
-        };
-        let tx_2 = TransactionWithMeta {
-            data: Transaction {
-                sender: account_1.address.clone(),
-                receiver: account_1.address.clone(),
-                amount: 5,
-            },
-            timestamp: 2,
-        };
-        let tx_3 = TransactionWithMeta {
-            data: Transaction {
-                sender: account_1.address.clone(),
-                receiver: account_0.address.clone(),
-                amount: 10,
-            },
-            timestamp: 2,
-        };
+    async fn get_rewards(&self, address: &Address) -> Result<Vec<BlockReward>> {
+        let data = self.data.lock().await;
+        let mut rewards = Vec::new();
+        let reward_index = parse_address(address).ok().and_then(|key| data.reward_index.get(&key));
+        for index in reward_index.into_iter().flatten() {
+            let block = data.blocks.get(&index.block_height).unwrap();
+            rewards.push(
+                BlockReward {
+                    slot: block.slot,
+                    timestamp: block.timestamp,
+                    reward: block.rewards.get(index.index).unwrap().map_addresses(&to_address),
+                }
+            );
+        }
+        Ok(rewards)
+    }
 
-        let block_0 = get_block(0, Vec::from([tx_0.data.clone()]));
-        let block_1 = get_block(1, Vec::from([tx_1.data.clone()]));
-        let block_2 = get_block(2, Vec::from([tx_2.data.clone(), tx_3.data.clone()]));
-
-        let mut expected_accounts = Vec::from([account_0, account_1]);
-
-        assert!(memory.add_block(block_0).await.is_ok());
-        expected_accounts[0].balance = -1;
-        expected_accounts[1].balance = 1;
-        assert_accounts(&memory, &expected_accounts).await;
-        assert_transactions(&memory, &expected_accounts[0].address, Vec::from([tx_0.clone()])).await;
-        assert_transactions(&memory, &expected_accounts[1].address, Vec::from([tx_0.clone()])).await;
-
-        assert!(memory.add_block(block_1).await.is_ok());
-        expected_accounts[0].balance = 1;
-        expected_accounts[1].balance = -1;
-        assert_accounts(&memory, &expected_accounts).await;
-        assert_transactions(&memory, &expected_accounts[0].address, Vec::from([tx_0.clone(), tx_1.clone()])).await;
-        assert_transactions(&memory, &expected_accounts[1].address, Vec::from([tx_0.clone(), tx_1.clone()])).await;
-
-        assert!(memory.add_block(block_2).await.is_ok());
-        expected_accounts[0].balance = 11;
-        expected_accounts[1].balance = -11;
-        assert_accounts(&memory, &expected_accounts).await;
-        assert_transactions(
-            &memory,
-            &expected_accounts[0].address,
-            Vec::from([tx_0.clone(),tx_1.clone(), tx_3.clone()])
-        ).await;
-        assert_transactions(
-            &memory,
-            &expected_accounts[1].address,
-            Vec::from([tx_0.clone(), tx_1.clone(), tx_2.clone(), tx_3.clone()])
-        ).await;
+    async fn get_block_rewards(&self, height: u64) -> Result<Option<Vec<Reward>>> {
+        let data = self.data.lock().await;
+        Ok(data.blocks.get(&height).map(|block| {
+            block.rewards.iter().map(|reward| reward.map_addresses(&to_address)).collect()
+        }))
     }
-    async fn test_add_block_failures() {
-        let mut memory = Memory::default();
 
-        let block_0 = get_block(0, Vec::new());
-        let block_1 = get_block(1, Vec::new());
-        let block_2 = get_block(2, Vec::new());
+    async fn get_last_slot(&self) -> Result<Option<Slot>> {
+        let data = self.data.lock().await;
+        Ok(data.blocks.get(&data.last_block).map(|block| block.slot))
+    }
 
-        assert!(memory.add_block(block_1.clone()).await.is_ok());
-        // Adding the block again should lead to failure
-        let expected_error = Error::InvalidBlock(1, "Already exists in storage".to_string());
-        match memory.add_block(block_1).await {
-            Err(Error::InvalidBlock(height, error)) => {
-                assert_eq!(height, 1);
-                assert_eq!(error, "Already exists in storage");
-            }
-            _ => {panic!("existing block test failed")}
-        }
-        match memory.add_block(block_0).await {
-            Err(Error::InvalidBlock(height, error)) => {
-                assert_eq!(height, 0);
-                assert_eq!(error, "Block height must be ascending. last_block: 1");
-            }
-            _ => {panic!("lower block height test failed")}
+    async fn get_last_block(&self) -> Result<Option<Block>> {
+        let data = self.data.lock().await;
+        Ok(data.blocks.get(&data.last_block).map(|block| block.map_addresses(&to_address)))
+    }
+
+    async fn get_blocks(&self, from: u64, to: u64) -> Result<Vec<Block>> {
+        let data = self.data.lock().await;
+        // Heights above the last block can't exist, which keeps open ranges cheap.
+        Ok((from..=to.min(data.last_block))
+            .filter_map(|height| data.blocks.get(&height).map(|block| block.map_addresses(&to_address)))
+            .collect())
+    }
+
+    async fn rollback_to(&mut self, slot: Slot) -> Result<()> {
+        let mut data = self.data.lock().await;
+        let mut heights: Vec<u64> = data.blocks.values()
+            .filter(|block| block.slot > slot)
+            .map(|block| block.height)
+            .collect();
+        heights.sort_unstable_by(|a, b| b.cmp(a));
+        for height in heights {
+            data.remove_block(height, self.accounting);
         }
+        data.last_block = data.blocks.keys().max().copied().unwrap_or_default();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::tests;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add_and_fetch_data() {
+        tests::add_and_fetch_data(&mut Memory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_add_block_failures() {
+        tests::add_block_failures(&mut Memory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_transactions_by_day() {
+        tests::transactions_by_day(&mut Memory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_last_slot() {
+        tests::last_slot(&mut Memory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_last_block() {
+        tests::last_block(&mut Memory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_blocks() {
+        tests::blocks(&mut Memory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_account_summary() {
+        tests::account_summary(&mut Memory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_pagination() {
+        tests::pagination(&mut Memory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_account_order() {
+        tests::account_order(&mut Memory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_transaction_filter() {
+        tests::transaction_filter(&mut Memory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_rollback() {
+        tests::rollback(&mut Memory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_initial_balances() {
+        tests::initial_balances(&mut Memory::default()).await;
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_balance_accounting() {
+        tests::balance_accounting(&mut Memory::new(AccountingMode::Balances)).await;
+    }
+
+    #[tokio::test]
+    async fn test_transaction_meta() {
+        tests::transaction_meta(&mut Memory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_token_balances() {
+        tests::token_balances(&mut Memory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_raw_instructions() {
+        tests::raw_instructions(&mut Memory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_custom_instructions() {
+        tests::custom_instructions(&mut Memory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_transactions_by_memo() {
+        tests::transactions_by_memo(&mut Memory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_stake_and_votes() {
+        tests::stake_and_votes(&mut Memory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_rewards() {
+        tests::rewards(&mut Memory::default()).await;
+    }
+}
@@ -1,13 +1,40 @@
 pub mod memory;
+pub mod sqlite;
+#[cfg(test)]
+mod tests;
 
 use crate::error::{Error, Result};
-use crate::types::{Account, Address, Block, TransactionWithMeta};
+use chrono::NaiveDate;
+use solana_sdk::clock::Slot;
+use serde::Deserialize;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use crate::types::{
+    Account, AccountSummary, Address, Block, BlockReward, CustomInstruction, RawInstruction, Reward, Signature,
+    StakeAccount, SystemInstructionKind, TokenBalance, TokenTransfer, Transaction, TransactionMeta, TransactionWithMeta,
+    Validator,
+};
 use tokio::sync::{mpsc, oneshot};
 use tokio_util::sync::CancellationToken;
 
 pub type AddBlockResult = Result<()>;
-pub type GetAccountsResult = Result<Vec<Account>>;
+pub type GetAccountsResult = Result<Paginated<Account, Address>>;
+pub type GetAccountResult = Result<Option<AccountSummary>>;
 pub type GetTransactionsResult = Result<Vec<TransactionWithMeta>>;
+pub type GetTransactionPageResult = Result<Paginated<TransactionWithMeta, TransactionCursor>>;
+pub type GetTokenBalancesResult = Result<Vec<TokenBalance>>;
+pub type GetTokenTransfersResult = Result<Vec<TransactionWithMeta<TokenTransfer>>>;
+pub type GetRawInstructionsResult = Result<Vec<TransactionWithMeta<RawInstruction>>>;
+pub type GetCustomInstructionsResult = Result<Vec<TransactionWithMeta<CustomInstruction>>>;
+pub type GetStakeAccountResult = Result<Option<StakeAccount>>;
+pub type GetValidatorResult = Result<Option<Validator>>;
+pub type GetRewardsResult = Result<Vec<BlockReward>>;
+pub type GetBlockRewardsResult = Result<Option<Vec<Reward>>>;
+pub type GetLastSlotResult = Result<Option<Slot>>;
+pub type GetBlockResult = Result<Option<Block>>;
+pub type GetBlocksResult = Result<Vec<Block>>;
+pub type RollbackResult = Result<()>;
 
 
 async fn receive<Type>(sender: &str, receiver: oneshot::Receiver<Type>) -> Result<Type> {
@@ -16,10 +43,217 @@ async fn receive<Type>(sender: &str, receiver: oneshot::Receiver<Type>) -> Resul
     })
 }
 
+/// The storage backends selectable via command line, either `memory` or `sqlite:<path>`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StorageType {
+    Memory,
+    Sqlite(PathBuf),
+}
+
+impl FromStr for StorageType {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        if value == "memory" {
+            return Ok(StorageType::Memory);
+        }
+        match value.strip_prefix("sqlite:") {
+            Some(path) if !path.is_empty() => Ok(StorageType::Sqlite(PathBuf::from(path))),
+            _ => Err(format!("Invalid storage '{}', expected memory or sqlite:<path>", value)),
+        }
+    }
+}
+
+/// How the storage computes the account balances.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AccountingMode {
+    /// Applies the amounts of the parsed transactions and the block rewards.
+    #[default]
+    Transfers,
+    /// Takes the balances from the balance changes of the blocks, which include fees, rewards and
+    /// the lamport movements of all programs.
+    Balances,
+}
+
+impl FromStr for AccountingMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "transfers" => Ok(AccountingMode::Transfers),
+            "balances" => Ok(AccountingMode::Balances),
+            _ => Err(format!("Invalid accounting mode '{}', expected transfers or balances", value)),
+        }
+    }
+}
+
+/// Matches the memo of a transaction either as a whole or by its beginning.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MemoFilter {
+    Exact(String),
+    Prefix(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusFilter {
+    Success,
+    Failed,
+}
+
+impl StatusFilter {
+    /// Transactions without metadata have an unknown status and never match.
+    pub fn matches<A>(&self, meta: Option<&TransactionMeta<A>>) -> bool {
+        meta.is_some_and(|meta| meta.status.is_success() == (*self == StatusFilter::Success))
+    }
+}
+
+/// The direction of a transaction from the point of view of the queried address.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Received from another address.
+    In,
+    /// Sent to another address.
+    Out,
+    /// Sent to the address itself.
+    #[serde(rename = "self")]
+    SelfTransfer,
+}
+
+/// Restricts the transactions of an address, all bounds are inclusive and unset fields match everything.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransactionFilter {
+    /// The earliest block timestamp in seconds since the epoch.
+    pub from: Option<i64>,
+    /// The latest block timestamp in seconds since the epoch.
+    pub to: Option<i64>,
+    pub min_amount: Option<u64>,
+    pub max_amount: Option<u64>,
+    pub direction: Option<Direction>,
+    pub status: Option<StatusFilter>,
+    pub kind: Option<SystemInstructionKind>,
+}
+
+impl TransactionFilter {
+    /// Whether `transaction` of a block with `timestamp` matches, which involves `address`.
+    pub fn matches_transaction<A: PartialEq>(
+        &self,
+        address: &A,
+        timestamp: i64,
+        transaction: &Transaction<A>,
+        meta: Option<&TransactionMeta<A>>,
+    ) -> bool {
+        let time = self.from.is_none_or(|from| timestamp >= from) && self.to.is_none_or(|to| timestamp <= to);
+        let amount = self.min_amount.is_none_or(|min_amount| transaction.amount >= min_amount)
+            && self.max_amount.is_none_or(|max_amount| transaction.amount <= max_amount);
+        let status = self.status.is_none_or(|status| status.matches(meta));
+        let kind = self.kind.is_none_or(|kind| transaction.kind == kind);
+        time && amount && status && kind && self.direction.is_none_or(|direction| {
+            let (sent, received) = (&transaction.sender == address, &transaction.receiver == address);
+            match direction {
+                Direction::In => received && !sent,
+                Direction::Out => sent && !received,
+                Direction::SelfTransfer => sent && received,
+            }
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Selects up to `limit` items following `cursor` in `order`, or the first ones without cursor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Page<Cursor> {
+    pub limit: usize,
+    pub cursor: Option<Cursor>,
+    pub order: Order,
+}
+
+impl<Cursor> Default for Page<Cursor> {
+    /// All items in ascending order.
+    fn default() -> Self {
+        Self {limit: usize::MAX, cursor: None, order: Order::Asc}
+    }
+}
+
+/// The items of a page together with the cursor of the next page, if there are more items.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Paginated<Item, Cursor> {
+    pub items: Vec<Item>,
+    pub next_cursor: Option<Cursor>,
+}
+
+impl<Item, Cursor> Paginated<Item, Cursor> {
+    /// Takes up to `limit` of the ordered `items`, which start after the cursor of the page.
+    pub fn collect(items: impl Iterator<Item = Item>, limit: usize, cursor: impl Fn(&Item) -> Cursor) -> Self {
+        let mut items: Vec<Item> = items.take(limit.saturating_add(1)).collect();
+        let mut next_cursor = None;
+        if items.len() > limit {
+            items.truncate(limit);
+            next_cursor = items.last().map(cursor);
+        }
+        Self {items, next_cursor}
+    }
+
+    pub fn map_cursor<Next>(self, map: impl FnOnce(Cursor) -> Next) -> Paginated<Item, Next> {
+        Paginated {items: self.items, next_cursor: self.next_cursor.map(map)}
+    }
+}
+
+/// The position of a transaction in the storage, which orders the transactions by block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TransactionCursor {
+    pub block_height: u64,
+    /// The index of the transaction in its block.
+    pub index: usize,
+}
+
+impl fmt::Display for TransactionCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.block_height, self.index)
+    }
+}
+
+impl FromStr for TransactionCursor {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || format!("Invalid transaction cursor '{}'", value);
+        let (block_height, index) = value.split_once('.').ok_or_else(invalid)?;
+        Ok(Self {
+            block_height: block_height.parse().map_err(|_| invalid())?,
+            index: index.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
 pub enum StorageCommand {
-    AddBlock(Block, oneshot::Sender<AddBlockResult>),
-    GetAccounts(oneshot::Sender<GetAccountsResult>),
-    GetTransactions(Address, oneshot::Sender<GetTransactionsResult>),
+    AddBlock(Box<Block>, oneshot::Sender<AddBlockResult>),
+    GetAccounts(Page<Address>, oneshot::Sender<GetAccountsResult>),
+    GetAccount(Address, oneshot::Sender<GetAccountResult>),
+    GetTransactions(Address, TransactionFilter, Page<TransactionCursor>, oneshot::Sender<GetTransactionPageResult>),
+    GetTransactionsBySignature(Signature, oneshot::Sender<GetTransactionsResult>),
+    GetTransactionsByDay(NaiveDate, oneshot::Sender<GetTransactionsResult>),
+    GetTransactionsByMemo(MemoFilter, oneshot::Sender<GetTransactionsResult>),
+    GetTokenBalances(Address, oneshot::Sender<GetTokenBalancesResult>),
+    GetTokenTransfersByMint(Address, oneshot::Sender<GetTokenTransfersResult>),
+    GetInstructionsByProgram(Address, oneshot::Sender<GetRawInstructionsResult>),
+    GetCustomInstructionsByProgram(Address, oneshot::Sender<GetCustomInstructionsResult>),
+    GetStakeAccount(Address, oneshot::Sender<GetStakeAccountResult>),
+    GetValidator(Address, oneshot::Sender<GetValidatorResult>),
+    GetRewards(Address, oneshot::Sender<GetRewardsResult>),
+    GetBlockRewards(u64, oneshot::Sender<GetBlockRewardsResult>),
+    GetLastSlot(oneshot::Sender<GetLastSlotResult>),
+    GetLastBlock(oneshot::Sender<GetBlockResult>),
+    GetBlocks(u64, u64, oneshot::Sender<GetBlocksResult>),
+    RollbackTo(Slot, oneshot::Sender<RollbackResult>),
 }
 
 impl StorageCommand {
@@ -42,36 +276,134 @@ impl StorageInterface {
     }
     pub async fn add_block(&self, block: Block) -> AddBlockResult {
         let (tx, rx) = oneshot::channel();
-        let command = StorageCommand::AddBlock(block, tx);
+        let command = StorageCommand::AddBlock(Box::new(block), tx);
         command.send("add_block", self.command_tx.clone()).await?;
         receive("add_block", rx).await?
     }
-    pub async fn get_accounts(&self) -> GetAccountsResult {
+    pub async fn get_accounts(&self, page: Page<Address>) -> GetAccountsResult {
         let (tx, rx) = oneshot::channel();
-        let command = StorageCommand::GetAccounts(tx);
+        let command = StorageCommand::GetAccounts(page, tx);
         command.send("get_accounts", self.command_tx.clone()).await?;
         receive("get_accounts", rx).await?
     }
-    pub async fn get_transactions(&self, address: Address) -> GetTransactionsResult {
+    pub async fn get_account(&self, address: Address) -> GetAccountResult {
+        let (tx, rx) = oneshot::channel();
+        let command = StorageCommand::GetAccount(address, tx);
+        command.send("get_account", self.command_tx.clone()).await?;
+        receive("get_account", rx).await?
+    }
+    pub async fn get_transactions(
+        &self,
+        address: Address,
+        filter: TransactionFilter,
+        page: Page<TransactionCursor>,
+    ) -> GetTransactionPageResult {
         let (tx, rx) = oneshot::channel();
-        let command = StorageCommand::GetTransactions(address, tx);
+        let command = StorageCommand::GetTransactions(address, filter, page, tx);
         command.send("get_transactions", self.command_tx.clone()).await?;
         receive("get_transactions", rx).await?
     }
+    pub async fn get_transactions_by_signature(&self, signature: Signature) -> GetTransactionsResult {
+        let (tx, rx) = oneshot::channel();
+        let command = StorageCommand::GetTransactionsBySignature(signature, tx);
+        command.send("get_transactions_by_signature", self.command_tx.clone()).await?;
+        receive("get_transactions_by_signature", rx).await?
+    }
+    pub async fn get_transactions_by_day(&self, day: NaiveDate) -> GetTransactionsResult {
+        let (tx, rx) = oneshot::channel();
+        let command = StorageCommand::GetTransactionsByDay(day, tx);
+        command.send("get_transactions_by_day", self.command_tx.clone()).await?;
+        receive("get_transactions_by_day", rx).await?
+    }
+    pub async fn get_transactions_by_memo(&self, filter: MemoFilter) -> GetTransactionsResult {
+        let (tx, rx) = oneshot::channel();
+        let command = StorageCommand::GetTransactionsByMemo(filter, tx);
+        command.send("get_transactions_by_memo", self.command_tx.clone()).await?;
+        receive("get_transactions_by_memo", rx).await?
+    }
+    pub async fn get_token_balances(&self, owner: Address) -> GetTokenBalancesResult {
+        let (tx, rx) = oneshot::channel();
+        let command = StorageCommand::GetTokenBalances(owner, tx);
+        command.send("get_token_balances", self.command_tx.clone()).await?;
+        receive("get_token_balances", rx).await?
+    }
+    pub async fn get_token_transfers_by_mint(&self, mint: Address) -> GetTokenTransfersResult {
+        let (tx, rx) = oneshot::channel();
+        let command = StorageCommand::GetTokenTransfersByMint(mint, tx);
+        command.send("get_token_transfers_by_mint", self.command_tx.clone()).await?;
+        receive("get_token_transfers_by_mint", rx).await?
+    }
+    pub async fn get_instructions_by_program(&self, program_id: Address) -> GetRawInstructionsResult {
+        let (tx, rx) = oneshot::channel();
+        let command = StorageCommand::GetInstructionsByProgram(program_id, tx);
+        command.send("get_instructions_by_program", self.command_tx.clone()).await?;
+        receive("get_instructions_by_program", rx).await?
+    }
+    pub async fn get_custom_instructions_by_program(&self, program_id: Address) -> GetCustomInstructionsResult {
+        let (tx, rx) = oneshot::channel();
+        let command = StorageCommand::GetCustomInstructionsByProgram(program_id, tx);
+        command.send("get_custom_instructions_by_program", self.command_tx.clone()).await?;
+        receive("get_custom_instructions_by_program", rx).await?
+    }
+    pub async fn get_stake_account(&self, address: Address) -> GetStakeAccountResult {
+        let (tx, rx) = oneshot::channel();
+        let command = StorageCommand::GetStakeAccount(address, tx);
+        command.send("get_stake_account", self.command_tx.clone()).await?;
+        receive("get_stake_account", rx).await?
+    }
+    pub async fn get_validator(&self, vote_account: Address) -> GetValidatorResult {
+        let (tx, rx) = oneshot::channel();
+        let command = StorageCommand::GetValidator(vote_account, tx);
+        command.send("get_validator", self.command_tx.clone()).await?;
+        receive("get_validator", rx).await?
+    }
+    pub async fn get_rewards(&self, address: Address) -> GetRewardsResult {
+        let (tx, rx) = oneshot::channel();
+        let command = StorageCommand::GetRewards(address, tx);
+        command.send("get_rewards", self.command_tx.clone()).await?;
+        receive("get_rewards", rx).await?
+    }
+    pub async fn get_block_rewards(&self, height: u64) -> GetBlockRewardsResult {
+        let (tx, rx) = oneshot::channel();
+        let command = StorageCommand::GetBlockRewards(height, tx);
+        command.send("get_block_rewards", self.command_tx.clone()).await?;
+        receive("get_block_rewards", rx).await?
+    }
+    pub async fn get_last_slot(&self) -> GetLastSlotResult {
+        let (tx, rx) = oneshot::channel();
+        let command = StorageCommand::GetLastSlot(tx);
+        command.send("get_last_slot", self.command_tx.clone()).await?;
+        receive("get_last_slot", rx).await?
+    }
+    pub async fn get_last_block(&self) -> GetBlockResult {
+        let (tx, rx) = oneshot::channel();
+        let command = StorageCommand::GetLastBlock(tx);
+        command.send("get_last_block", self.command_tx.clone()).await?;
+        receive("get_last_block", rx).await?
+    }
+    pub async fn get_blocks(&self, from: u64, to: u64) -> GetBlocksResult {
+        let (tx, rx) = oneshot::channel();
+        let command = StorageCommand::GetBlocks(from, to, tx);
+        command.send("get_blocks", self.command_tx.clone()).await?;
+        receive("get_blocks", rx).await?
+    }
+    pub async fn rollback_to(&self, slot: Slot) -> RollbackResult {
+        let (tx, rx) = oneshot::channel();
+        let command = StorageCommand::RollbackTo(slot, tx);
+        command.send("rollback_to", self.command_tx.clone()).await?;
+        receive("rollback_to", rx).await?
+    }
 }
 
+// The storages are only run as their concrete types, whose futures are known to be `Send`.
+#[allow(async_fn_in_trait)]
 pub trait Storage {
     async fn run(&mut self, mut receiver: mpsc::Receiver<StorageCommand>, token: CancellationToken) -> Result<()> {
         loop {
             tokio::select! {
                 command = receiver.recv() => {
-                    match command {
-                        Some(command) => {
-                            self.process_command(command).await?
-                        }
-                        None => {
-
-                        }
+                    if let Some(command) = command {
+                        self.process_command(command).await?
                     }
                     continue
                 },
@@ -86,7 +418,7 @@ pub trait Storage {
     async fn process_command(&mut self, command: StorageCommand) -> Result<()> {
         match command {
             StorageCommand::AddBlock(block, sender) => {
-                if let Err(_) = sender.send(self.add_block(block).await) {
+                if sender.send(self.add_block(*block).await).is_err() {
                     return Err(
                         Error::ChannelFailure(
                             "storage_add_block".to_string(),
@@ -95,26 +427,198 @@ pub trait Storage {
                     )
                 }
             }
-            StorageCommand::GetAccounts(sender) => {
-                if let Err(_) = sender.send(self.get_accounts().await) {
+            StorageCommand::GetAccounts(page, sender) => {
+                if sender.send(self.get_accounts(&page).await).is_err() {
                     return Err(Error::ChannelFailure(
                         "storage_get_accounts".to_string(),
                         "send failure".to_string())
                     )
                 }
             }
-            StorageCommand::GetTransactions(address, sender) => {
-                if let Err(_) = sender.send(self.get_transactions(&address).await) {
+            StorageCommand::GetAccount(address, sender) => {
+                if sender.send(self.get_account(&address).await).is_err() {
+                    return Err(Error::ChannelFailure(
+                        "storage_get_account".to_string(),
+                        "send failure".to_string())
+                    )
+                }
+            }
+            StorageCommand::GetTransactions(address, filter, page, sender) => {
+                if sender.send(self.get_transactions(&address, &filter, &page).await).is_err() {
                     return Err(Error::ChannelFailure(
                         "storage_get_transactions".to_string(),
                         "send failure".to_string())
                     )
                 }
             }
+            StorageCommand::GetTransactionsBySignature(signature, sender) => {
+                if sender.send(self.get_transactions_by_signature(&signature).await).is_err() {
+                    return Err(Error::ChannelFailure(
+                        "storage_get_transactions_by_signature".to_string(),
+                        "send failure".to_string())
+                    )
+                }
+            }
+            StorageCommand::GetTransactionsByDay(day, sender) => {
+                if sender.send(self.get_transactions_by_day(&day).await).is_err() {
+                    return Err(Error::ChannelFailure(
+                        "storage_get_transactions_by_day".to_string(),
+                        "send failure".to_string())
+                    )
+                }
+            }
+            StorageCommand::GetTransactionsByMemo(filter, sender) => {
+                if sender.send(self.get_transactions_by_memo(&filter).await).is_err() {
+                    return Err(Error::ChannelFailure(
+                        "storage_get_transactions_by_memo".to_string(),
+                        "send failure".to_string())
+                    )
+                }
+            }
+            StorageCommand::GetTokenBalances(owner, sender) => {
+                if sender.send(self.get_token_balances(&owner).await).is_err() {
+                    return Err(Error::ChannelFailure(
+                        "storage_get_token_balances".to_string(),
+                        "send failure".to_string())
+                    )
+                }
+            }
+            StorageCommand::GetTokenTransfersByMint(mint, sender) => {
+                if sender.send(self.get_token_transfers_by_mint(&mint).await).is_err() {
+                    return Err(Error::ChannelFailure(
+                        "storage_get_token_transfers_by_mint".to_string(),
+                        "send failure".to_string())
+                    )
+                }
+            }
+            StorageCommand::GetInstructionsByProgram(program_id, sender) => {
+                if sender.send(self.get_instructions_by_program(&program_id).await).is_err() {
+                    return Err(Error::ChannelFailure(
+                        "storage_get_instructions_by_program".to_string(),
+                        "send failure".to_string())
+                    )
+                }
+            }
+            StorageCommand::GetCustomInstructionsByProgram(program_id, sender) => {
+                if sender.send(self.get_custom_instructions_by_program(&program_id).await).is_err() {
+                    return Err(Error::ChannelFailure(
+                        "storage_get_custom_instructions_by_program".to_string(),
+                        "send failure".to_string())
+                    )
+                }
+            }
+            StorageCommand::GetStakeAccount(address, sender) => {
+                if sender.send(self.get_stake_account(&address).await).is_err() {
+                    return Err(Error::ChannelFailure(
+                        "storage_get_stake_account".to_string(),
+                        "send failure".to_string())
+                    )
+                }
+            }
+            StorageCommand::GetValidator(vote_account, sender) => {
+                if sender.send(self.get_validator(&vote_account).await).is_err() {
+                    return Err(Error::ChannelFailure(
+                        "storage_get_validator".to_string(),
+                        "send failure".to_string())
+                    )
+                }
+            }
+            StorageCommand::GetRewards(address, sender) => {
+                if sender.send(self.get_rewards(&address).await).is_err() {
+                    return Err(Error::ChannelFailure(
+                        "storage_get_rewards".to_string(),
+                        "send failure".to_string())
+                    )
+                }
+            }
+            StorageCommand::GetBlockRewards(height, sender) => {
+                if sender.send(self.get_block_rewards(height).await).is_err() {
+                    return Err(Error::ChannelFailure(
+                        "storage_get_block_rewards".to_string(),
+                        "send failure".to_string())
+                    )
+                }
+            }
+            StorageCommand::GetLastSlot(sender) => {
+                if sender.send(self.get_last_slot().await).is_err() {
+                    return Err(Error::ChannelFailure(
+                        "storage_get_last_slot".to_string(),
+                        "send failure".to_string())
+                    )
+                }
+            }
+            StorageCommand::GetLastBlock(sender) => {
+                if sender.send(self.get_last_block().await).is_err() {
+                    return Err(Error::ChannelFailure(
+                        "storage_get_last_block".to_string(),
+                        "send failure".to_string())
+                    )
+                }
+            }
+            StorageCommand::GetBlocks(from, to, sender) => {
+                if sender.send(self.get_blocks(from, to).await).is_err() {
+                    return Err(Error::ChannelFailure(
+                        "storage_get_blocks".to_string(),
+                        "send failure".to_string())
+                    )
+                }
+            }
+            StorageCommand::RollbackTo(slot, sender) => {
+                if sender.send(self.rollback_to(slot).await).is_err() {
+                    return Err(Error::ChannelFailure(
+                        "storage_rollback_to".to_string(),
+                        "send failure".to_string())
+                    )
+                }
+            }
         }
         Ok(())
     }
     async fn add_block(&mut self, block: Block) -> Result<()>;
-    async fn get_accounts(&self) -> Result<Vec<Account>>;
-    async fn get_transactions(&self, address: &Address) -> Result<Vec<TransactionWithMeta>>;
+    /// The accounts ordered by address.
+    async fn get_accounts(&self, page: &Page<Address>) -> Result<Paginated<Account, Address>>;
+    /// The summary of `address`, or `None` if it has neither a balance nor transactions.
+    async fn get_account(&self, address: &Address) -> Result<Option<AccountSummary>>;
+    /// The transactions involving `address` which match `filter`, ordered by block.
+    async fn get_transactions(
+        &self,
+        address: &Address,
+        filter: &TransactionFilter,
+        page: &Page<TransactionCursor>,
+    ) -> Result<Paginated<TransactionWithMeta, TransactionCursor>>;
+    async fn get_transactions_by_signature(&self, signature: &Signature) -> Result<Vec<TransactionWithMeta>>;
+    async fn get_transactions_by_day(&self, day: &NaiveDate) -> Result<Vec<TransactionWithMeta>>;
+    /// The transactions of all on-chain transactions with a memo matching `filter`.
+    async fn get_transactions_by_memo(&self, filter: &MemoFilter) -> Result<Vec<TransactionWithMeta>>;
+    /// The token balances of all token accounts of `owner`, summed up per mint.
+    async fn get_token_balances(&self, owner: &Address) -> Result<Vec<TokenBalance>>;
+    async fn get_token_transfers_by_mint(&self, mint: &Address) -> Result<Vec<TransactionWithMeta<TokenTransfer>>>;
+    /// The raw instructions of `program_id`, see [`RawInstruction`].
+    async fn get_instructions_by_program(
+        &self,
+        program_id: &Address,
+    ) -> Result<Vec<TransactionWithMeta<RawInstruction>>>;
+    /// The instructions of `program_id` a registered parser decoded, see [`CustomInstruction`].
+    async fn get_custom_instructions_by_program(
+        &self,
+        program_id: &Address,
+    ) -> Result<Vec<TransactionWithMeta<CustomInstruction>>>;
+    /// The stake instructions involving `address` and its delegation, or `None` if there aren't
+    /// any instructions.
+    async fn get_stake_account(&self, address: &Address) -> Result<Option<StakeAccount>>;
+    /// The votes of `vote_account` and the stake accounts delegated to it, or `None` if there
+    /// aren't any.
+    async fn get_validator(&self, vote_account: &Address) -> Result<Option<Validator>>;
+    /// All rewards credited to `address`, ordered by block.
+    async fn get_rewards(&self, address: &Address) -> Result<Vec<BlockReward>>;
+    /// The rewards of the block with `height`, or `None` if there is no such block.
+    async fn get_block_rewards(&self, height: u64) -> Result<Option<Vec<Reward>>>;
+    /// The slot of the last block added to the storage or `None` if there isn't any.
+    async fn get_last_slot(&self) -> Result<Option<Slot>>;
+    /// The last block added to the storage or `None` if there isn't any.
+    async fn get_last_block(&self) -> Result<Option<Block>>;
+    /// The blocks with a height from `from` to `to`, both inclusive, ordered by height.
+    async fn get_blocks(&self, from: u64, to: u64) -> Result<Vec<Block>>;
+    /// Reverts all blocks with a slot above `slot`, as if they were never added.
+    async fn rollback_to(&mut self, slot: Slot) -> Result<()>;
 }
\ No newline at end of file
@@ -0,0 +1,1684 @@
+use crate::error::{Error, Result};
+use crate::storage::{
+    AccountingMode, Direction, MemoFilter, Order, Page, Paginated, StatusFilter, Storage, TransactionCursor,
+    TransactionFilter,
+};
+use crate::types::{
+    parse_address, Account, AccountSummary, Address, BalanceChange, Block, BlockReward, CustomInstruction, Invocation,
+    RawInstruction, Reward, Signature, StakeAccount, StakeInstruction, TokenBalance, TokenBalanceChange, TokenTransfer,
+    Transaction, TransactionMeta, TransactionStatus, TransactionWithMeta, Validator, Vote,
+};
+use chrono::NaiveDate;
+use rusqlite::OptionalExtension;
+use solana_sdk::clock::Slot;
+use rusqlite::{params, Connection, Params, Row, ToSql};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Schema migrations, applied in order. The index of the last applied migration + 1 is stored as
+/// `user_version` in the database, so new migrations must only ever be appended here.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE blocks (
+        height INTEGER PRIMARY KEY,
+        hash TEXT NOT NULL,
+        timestamp INTEGER NOT NULL
+    );
+    CREATE INDEX blocks_timestamp ON blocks(timestamp);
+    CREATE TABLE transactions (
+        block_height INTEGER NOT NULL REFERENCES blocks(height),
+        idx INTEGER NOT NULL,
+        signature TEXT NOT NULL,
+        instruction_index INTEGER NOT NULL,
+        sender TEXT NOT NULL,
+        receiver TEXT NOT NULL,
+        amount INTEGER NOT NULL,
+        PRIMARY KEY (block_height, idx)
+    );
+    CREATE INDEX transactions_signature ON transactions(signature);
+    CREATE TABLE accounts (
+        address TEXT PRIMARY KEY,
+        balance INTEGER NOT NULL
+    );
+    CREATE TABLE address_index (
+        address TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+        idx INTEGER NOT NULL,
+        PRIMARY KEY (address, block_height, idx)
+    );",
+    // Blocks stored before the slot was tracked don't have one.
+    "ALTER TABLE blocks ADD COLUMN slot INTEGER;",
+    "ALTER TABLE blocks ADD COLUMN parent_slot INTEGER;
+    ALTER TABLE blocks ADD COLUMN previous_blockhash TEXT;",
+    "CREATE INDEX blocks_slot ON blocks(slot);",
+    "CREATE TABLE balance_changes (
+        block_height INTEGER NOT NULL REFERENCES blocks(height),
+        idx INTEGER NOT NULL,
+        address TEXT NOT NULL,
+        pre_balance INTEGER NOT NULL,
+        post_balance INTEGER NOT NULL,
+        PRIMARY KEY (block_height, idx)
+    );",
+    // A NULL error means the transaction succeeded.
+    "CREATE TABLE transaction_metas (
+        block_height INTEGER NOT NULL REFERENCES blocks(height),
+        idx INTEGER NOT NULL,
+        signature TEXT NOT NULL,
+        fee INTEGER NOT NULL,
+        fee_payer TEXT NOT NULL,
+        error TEXT,
+        compute_units_consumed INTEGER,
+        PRIMARY KEY (block_height, idx)
+    );
+    CREATE INDEX transaction_metas_signature ON transaction_metas(block_height, signature);",
+    // Token amounts can exceed the range of INTEGER and are stored as TEXT.
+    "CREATE TABLE token_transfers (
+        block_height INTEGER NOT NULL REFERENCES blocks(height),
+        idx INTEGER NOT NULL,
+        signature TEXT NOT NULL,
+        instruction_index INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        mint TEXT NOT NULL,
+        decimals INTEGER NOT NULL,
+        source TEXT,
+        destination TEXT,
+        amount TEXT NOT NULL,
+        PRIMARY KEY (block_height, idx)
+    );
+    CREATE INDEX token_transfers_mint ON token_transfers(mint);
+    CREATE TABLE token_balance_changes (
+        block_height INTEGER NOT NULL REFERENCES blocks(height),
+        idx INTEGER NOT NULL,
+        account TEXT NOT NULL,
+        owner TEXT NOT NULL,
+        mint TEXT NOT NULL,
+        decimals INTEGER NOT NULL,
+        pre_amount TEXT,
+        post_amount TEXT,
+        PRIMARY KEY (block_height, idx)
+    );
+    CREATE TABLE token_accounts (
+        address TEXT PRIMARY KEY,
+        owner TEXT NOT NULL,
+        mint TEXT NOT NULL,
+        decimals INTEGER NOT NULL,
+        amount TEXT NOT NULL
+    );
+    CREATE INDEX token_accounts_owner ON token_accounts(owner);",
+    // Only transfers were stored before the instruction kind was tracked.
+    "ALTER TABLE transactions ADD COLUMN kind TEXT NOT NULL DEFAULT 'transfer';",
+    // Both are NULL for outer instructions.
+    "ALTER TABLE transactions ADD COLUMN invoking_program TEXT;
+    ALTER TABLE transactions ADD COLUMN inner_index INTEGER;
+    ALTER TABLE token_transfers ADD COLUMN invoking_program TEXT;
+    ALTER TABLE token_transfers ADD COLUMN inner_index INTEGER;",
+    // The data is stored as JSON.
+    "CREATE TABLE custom_instructions (
+        block_height INTEGER NOT NULL REFERENCES blocks(height),
+        idx INTEGER NOT NULL,
+        signature TEXT NOT NULL,
+        instruction_index INTEGER NOT NULL,
+        program_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        data TEXT NOT NULL,
+        invoking_program TEXT,
+        inner_index INTEGER,
+        PRIMARY KEY (block_height, idx)
+    );
+    CREATE INDEX custom_instructions_program_id ON custom_instructions(program_id);",
+    // The accounts are stored as JSON array.
+    "CREATE TABLE raw_instructions (
+        block_height INTEGER NOT NULL REFERENCES blocks(height),
+        idx INTEGER NOT NULL,
+        signature TEXT NOT NULL,
+        instruction_index INTEGER NOT NULL,
+        program_id TEXT NOT NULL,
+        accounts TEXT NOT NULL,
+        data TEXT NOT NULL,
+        invoking_program TEXT,
+        inner_index INTEGER,
+        PRIMARY KEY (block_height, idx)
+    );
+    CREATE INDEX raw_instructions_program_id ON raw_instructions(program_id);",
+    "ALTER TABLE transaction_metas ADD COLUMN memo TEXT;
+    CREATE INDEX transaction_metas_memo ON transaction_metas(memo);",
+    "CREATE TABLE stake_instructions (
+        block_height INTEGER NOT NULL REFERENCES blocks(height),
+        idx INTEGER NOT NULL,
+        signature TEXT NOT NULL,
+        instruction_index INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        stake_account TEXT NOT NULL,
+        vote_account TEXT,
+        destination TEXT,
+        amount INTEGER NOT NULL,
+        invoking_program TEXT,
+        inner_index INTEGER,
+        PRIMARY KEY (block_height, idx)
+    );
+    CREATE INDEX stake_instructions_stake_account ON stake_instructions(stake_account);
+    CREATE INDEX stake_instructions_vote_account ON stake_instructions(vote_account);
+    CREATE INDEX stake_instructions_destination ON stake_instructions(destination);
+    CREATE TABLE votes (
+        block_height INTEGER NOT NULL REFERENCES blocks(height),
+        idx INTEGER NOT NULL,
+        signature TEXT NOT NULL,
+        instruction_index INTEGER NOT NULL,
+        vote_account TEXT NOT NULL,
+        authority TEXT NOT NULL,
+        slot INTEGER,
+        invoking_program TEXT,
+        inner_index INTEGER,
+        PRIMARY KEY (block_height, idx)
+    );
+    CREATE INDEX votes_vote_account ON votes(vote_account);",
+    "CREATE TABLE rewards (
+        block_height INTEGER NOT NULL REFERENCES blocks(height),
+        idx INTEGER NOT NULL,
+        address TEXT NOT NULL,
+        lamports INTEGER NOT NULL,
+        post_balance INTEGER NOT NULL,
+        kind TEXT,
+        commission INTEGER,
+        PRIMARY KEY (block_height, idx)
+    );
+    CREATE INDEX rewards_address ON rewards(address);",
+    // The accounts are ordered by the bytes of their public keys, like in the memory storage. The
+    // keys of the accounts stored before are filled in by `fill_account_keys`.
+    "ALTER TABLE accounts ADD COLUMN key BLOB;
+    CREATE INDEX accounts_key ON accounts(key);",
+];
+
+const TRANSACTION_COLUMNS: &str =
+    "signature, instruction_index, kind, sender, receiver, amount, invoking_program, inner_index";
+
+/// The position of the transaction in the storage is selected last, for the cursors of the pages.
+const SELECT_TRANSACTIONS: &str = "
+    SELECT t.signature, t.instruction_index, t.kind, t.sender, t.receiver, t.amount, t.invoking_program,
+        t.inner_index, b.timestamp, m.fee, m.fee_payer, m.error, m.compute_units_consumed,
+        m.memo, t.block_height, t.idx
+    FROM transactions t
+    JOIN blocks b ON b.height = t.block_height
+    LEFT JOIN transaction_metas m ON m.block_height = t.block_height AND m.signature = t.signature";
+
+/// Failed transactions don't change any balance besides their fee.
+const SUCCESSFUL_TRANSACTION: &str = "NOT EXISTS (
+    SELECT 1 FROM transaction_metas m
+    WHERE m.block_height = t.block_height AND m.signature = t.signature AND m.error IS NOT NULL
+)";
+
+/// The SQL `LIMIT` of a page, which fetches one more row to know if there is a next page.
+fn page_limit<Cursor>(page: &Page<Cursor>) -> i64 {
+    page.limit.saturating_add(1).min(i64::MAX as usize) as i64
+}
+
+/// The comparison operator and the direction of `ORDER BY` to continue a page after its cursor.
+fn page_order<Cursor>(page: &Page<Cursor>) -> (&'static str, &'static str) {
+    match page.order {
+        Order::Asc => (">", "ASC"),
+        Order::Desc => ("<", "DESC"),
+    }
+}
+
+/// The 32 bytes of the public key of an address, which `add_block` made sure is valid.
+fn account_key(address: &Address) -> [u8; 32] {
+    parse_address(address).unwrap().to_bytes()
+}
+
+/// Fills the keys of the accounts which were stored before the accounts had one. Addresses which
+/// are no valid public keys, and could only be stored before they were validated, keep none.
+fn fill_account_keys(transaction: &rusqlite::Transaction) -> Result<()> {
+    let addresses = {
+        let mut statement = transaction.prepare("SELECT address FROM accounts WHERE key IS NULL")?;
+        let addresses = statement.query_map([], |row| row.get::<_, Address>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        addresses
+    };
+    let mut update_key = transaction.prepare("UPDATE accounts SET key = ?2 WHERE address = ?1")?;
+    for address in addresses {
+        match parse_address(&address) {
+            Ok(key) => {
+                update_key.execute(params![address, key.to_bytes()])?;
+            }
+            Err(error) => log::warn!("Account without key {}: {}", address, error),
+        }
+    }
+    Ok(())
+}
+
+fn migrate(connection: &mut Connection) -> Result<()> {
+    let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    let transaction = connection.transaction()?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        log::debug!("Apply migration {}", index);
+        transaction.execute_batch(migration)?;
+    }
+    if version < MIGRATIONS.len() {
+        fill_account_keys(&transaction)?;
+    }
+    transaction.pragma_update(None, "user_version", MIGRATIONS.len())?;
+    transaction.commit()?;
+    Ok(())
+}
+
+fn token_amount_from_row(row: &Row, index: usize) -> rusqlite::Result<Option<u64>> {
+    match row.get::<_, Option<String>>(index)? {
+        Some(amount) => amount.parse().map(Some).map_err(|error| {
+            rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(error))
+        }),
+        None => Ok(None),
+    }
+}
+
+/// Unit enums are stored with their serde name.
+fn enum_to_sql<T: Serialize>(value: T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => unreachable!("Only unit enums are stored by name"),
+    }
+}
+
+fn enum_from_row<T: DeserializeOwned>(row: &Row, index: usize) -> rusqlite::Result<T> {
+    serde_json::from_value(serde_json::Value::String(row.get(index)?)).map_err(|error| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(error))
+    })
+}
+
+fn invocation_from_row(row: &Row, index: usize) -> rusqlite::Result<Option<Invocation>> {
+    match (row.get::<_, Option<Address>>(index)?, row.get::<_, Option<usize>>(index + 1)?) {
+        (Some(program_id), Some(inner_index)) => Ok(Some(Invocation {program_id, inner_index})),
+        _ => Ok(None),
+    }
+}
+
+fn invocation_to_sql(invocation: &Option<Invocation>) -> (Option<&Address>, Option<usize>) {
+    match invocation {
+        Some(invocation) => (Some(&invocation.program_id), Some(invocation.inner_index)),
+        None => (None, None),
+    }
+}
+
+const TOKEN_TRANSFER_COLUMNS: &str =
+    "signature, instruction_index, kind, mint, decimals, source, destination, amount, invoking_program, inner_index";
+
+const SELECT_TOKEN_TRANSFERS: &str = "
+    SELECT t.signature, t.instruction_index, t.kind, t.mint, t.decimals, t.source, t.destination, t.amount,
+        t.invoking_program, t.inner_index, b.timestamp, m.fee, m.fee_payer, m.error, m.compute_units_consumed,
+        m.memo
+    FROM token_transfers t
+    JOIN blocks b ON b.height = t.block_height
+    LEFT JOIN transaction_metas m ON m.block_height = t.block_height AND m.signature = t.signature";
+
+fn token_transfer_from_row(row: &Row) -> rusqlite::Result<TokenTransfer> {
+    Ok(
+        TokenTransfer {
+            signature: row.get(0)?,
+            instruction_index: row.get(1)?,
+            invocation: invocation_from_row(row, 8)?,
+            kind: enum_from_row(row, 2)?,
+            mint: row.get(3)?,
+            decimals: row.get(4)?,
+            source: row.get(5)?,
+            destination: row.get(6)?,
+            amount: token_amount_from_row(row, 7)?.unwrap_or_default(),
+        }
+    )
+}
+
+const RAW_INSTRUCTION_COLUMNS: &str =
+    "signature, instruction_index, program_id, accounts, data, invoking_program, inner_index";
+
+const SELECT_RAW_INSTRUCTIONS: &str = "
+    SELECT t.signature, t.instruction_index, t.program_id, t.accounts, t.data, t.invoking_program, t.inner_index,
+        b.timestamp, m.fee, m.fee_payer, m.error, m.compute_units_consumed,
+        m.memo
+    FROM raw_instructions t
+    JOIN blocks b ON b.height = t.block_height
+    LEFT JOIN transaction_metas m ON m.block_height = t.block_height AND m.signature = t.signature";
+
+fn raw_instruction_from_row(row: &Row) -> rusqlite::Result<RawInstruction> {
+    let accounts: String = row.get(3)?;
+    Ok(
+        RawInstruction {
+            signature: row.get(0)?,
+            instruction_index: row.get(1)?,
+            invocation: invocation_from_row(row, 5)?,
+            program_id: row.get(2)?,
+            accounts: serde_json::from_str(&accounts).map_err(|error| {
+                rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(error))
+            })?,
+            data: row.get(4)?,
+        }
+    )
+}
+
+const CUSTOM_INSTRUCTION_COLUMNS: &str =
+    "signature, instruction_index, program_id, name, data, invoking_program, inner_index";
+
+const SELECT_CUSTOM_INSTRUCTIONS: &str = "
+    SELECT t.signature, t.instruction_index, t.program_id, t.name, t.data, t.invoking_program, t.inner_index,
+        b.timestamp, m.fee, m.fee_payer, m.error, m.compute_units_consumed,
+        m.memo
+    FROM custom_instructions t
+    JOIN blocks b ON b.height = t.block_height
+    LEFT JOIN transaction_metas m ON m.block_height = t.block_height AND m.signature = t.signature";
+
+fn custom_instruction_from_row(row: &Row) -> rusqlite::Result<CustomInstruction> {
+    let data: String = row.get(4)?;
+    Ok(
+        CustomInstruction {
+            signature: row.get(0)?,
+            instruction_index: row.get(1)?,
+            invocation: invocation_from_row(row, 5)?,
+            program_id: row.get(2)?,
+            name: row.get(3)?,
+            data: serde_json::from_str(&data).map_err(|error| {
+                rusqlite::Error::FromSqlConversionFailure(4, rusqlite::types::Type::Text, Box::new(error))
+            })?,
+        }
+    )
+}
+
+const STAKE_INSTRUCTION_COLUMNS: &str =
+    "signature, instruction_index, kind, stake_account, vote_account, destination, amount, invoking_program, \
+    inner_index";
+
+const SELECT_STAKE_INSTRUCTIONS: &str = "
+    SELECT t.signature, t.instruction_index, t.kind, t.stake_account, t.vote_account, t.destination, t.amount,
+        t.invoking_program, t.inner_index, b.timestamp, m.fee, m.fee_payer, m.error, m.compute_units_consumed,
+        m.memo
+    FROM stake_instructions t
+    JOIN blocks b ON b.height = t.block_height
+    LEFT JOIN transaction_metas m ON m.block_height = t.block_height AND m.signature = t.signature";
+
+fn stake_instruction_from_row(row: &Row) -> rusqlite::Result<StakeInstruction> {
+    Ok(
+        StakeInstruction {
+            signature: row.get(0)?,
+            instruction_index: row.get(1)?,
+            invocation: invocation_from_row(row, 7)?,
+            kind: enum_from_row(row, 2)?,
+            stake_account: row.get(3)?,
+            vote_account: row.get(4)?,
+            destination: row.get(5)?,
+            amount: row.get(6)?,
+        }
+    )
+}
+
+const VOTE_COLUMNS: &str = "signature, instruction_index, vote_account, authority, slot, invoking_program, inner_index";
+
+fn vote_from_row(row: &Row) -> rusqlite::Result<Vote> {
+    Ok(
+        Vote {
+            signature: row.get(0)?,
+            instruction_index: row.get(1)?,
+            invocation: invocation_from_row(row, 5)?,
+            vote_account: row.get(2)?,
+            authority: row.get(3)?,
+            slot: row.get(4)?,
+        }
+    )
+}
+
+const REWARD_COLUMNS: &str = "address, lamports, post_balance, kind, commission";
+
+fn reward_from_row(row: &Row) -> rusqlite::Result<Reward> {
+    let kind: Option<String> = row.get(3)?;
+    Ok(
+        Reward {
+            address: row.get(0)?,
+            lamports: row.get(1)?,
+            post_balance: row.get(2)?,
+            kind: match kind {
+                Some(_) => Some(enum_from_row(row, 3)?),
+                None => None,
+            },
+            commission: row.get(4)?,
+        }
+    )
+}
+
+fn status_from_error(error: Option<String>) -> TransactionStatus {
+    match error {
+        Some(error) => TransactionStatus::Failed(error),
+        None => TransactionStatus::Success,
+    }
+}
+
+/// Reads the columns of a LEFT JOINed `transaction_metas` row starting at `index`.
+fn transaction_meta_from_row(
+    row: &Row,
+    signature: &Signature,
+    index: usize,
+) -> rusqlite::Result<Option<TransactionMeta>> {
+    let Some(fee) = row.get::<_, Option<u64>>(index)? else {
+        return Ok(None);
+    };
+    Ok(Some(
+        TransactionMeta {
+            signature: signature.clone(),
+            fee,
+            fee_payer: row.get(index + 1)?,
+            status: status_from_error(row.get(index + 2)?),
+            compute_units_consumed: row.get(index + 3)?,
+            memo: row.get(index + 4)?,
+        }
+    ))
+}
+
+fn system_transaction_from_row(row: &Row) -> rusqlite::Result<Transaction> {
+    Ok(
+        Transaction {
+            signature: row.get(0)?,
+            instruction_index: row.get(1)?,
+            invocation: invocation_from_row(row, 6)?,
+            kind: enum_from_row(row, 2)?,
+            sender: row.get(3)?,
+            receiver: row.get(4)?,
+            amount: row.get(5)?,
+        }
+    )
+}
+
+fn transaction_from_row(row: &Row) -> rusqlite::Result<TransactionWithMeta> {
+    let data = system_transaction_from_row(row)?;
+    let meta = transaction_meta_from_row(row, &data.signature, 9)?;
+    Ok(TransactionWithMeta {data, timestamp: row.get(8)?, meta})
+}
+
+fn query_transactions<P: Params>(
+    connection: &Connection,
+    query: &str,
+    params: P,
+) -> Result<Vec<TransactionWithMeta>> {
+    let mut statement = connection.prepare_cached(query)?;
+    let transactions = statement.query_map(params, transaction_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(transactions)
+}
+
+fn query_token_transfers<P: Params>(
+    connection: &Connection,
+    query: &str,
+    params: P,
+) -> Result<Vec<TransactionWithMeta<TokenTransfer>>> {
+    let mut statement = connection.prepare_cached(query)?;
+    let transfers = statement.query_map(params, |row| {
+        let data = token_transfer_from_row(row)?;
+        let meta = transaction_meta_from_row(row, &data.signature, 11)?;
+        Ok(TransactionWithMeta {data, timestamp: row.get(10)?, meta})
+    })?.collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(transfers)
+}
+
+fn query_raw_instructions<P: Params>(
+    connection: &Connection,
+    query: &str,
+    params: P,
+) -> Result<Vec<TransactionWithMeta<RawInstruction>>> {
+    let mut statement = connection.prepare_cached(query)?;
+    let instructions = statement.query_map(params, |row| {
+        let data = raw_instruction_from_row(row)?;
+        let meta = transaction_meta_from_row(row, &data.signature, 8)?;
+        Ok(TransactionWithMeta {data, timestamp: row.get(7)?, meta})
+    })?.collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(instructions)
+}
+
+fn query_custom_instructions<P: Params>(
+    connection: &Connection,
+    query: &str,
+    params: P,
+) -> Result<Vec<TransactionWithMeta<CustomInstruction>>> {
+    let mut statement = connection.prepare_cached(query)?;
+    let instructions = statement.query_map(params, |row| {
+        let data = custom_instruction_from_row(row)?;
+        let meta = transaction_meta_from_row(row, &data.signature, 8)?;
+        Ok(TransactionWithMeta {data, timestamp: row.get(7)?, meta})
+    })?.collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(instructions)
+}
+
+fn query_stake_instructions<P: Params>(
+    connection: &Connection,
+    query: &str,
+    params: P,
+) -> Result<Vec<TransactionWithMeta<StakeInstruction>>> {
+    let mut statement = connection.prepare_cached(query)?;
+    let instructions = statement.query_map(params, |row| {
+        let data = stake_instruction_from_row(row)?;
+        let meta = transaction_meta_from_row(row, &data.signature, 10)?;
+        Ok(TransactionWithMeta {data, timestamp: row.get(9)?, meta})
+    })?.collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(instructions)
+}
+
+fn load_stake_account(connection: &Connection, address: &Address) -> Result<Option<StakeAccount>> {
+    let instructions = query_stake_instructions(
+        connection,
+        &format!(
+            "{} WHERE t.stake_account = ?1 OR t.destination = ?1 ORDER BY t.block_height, t.idx",
+            SELECT_STAKE_INSTRUCTIONS
+        ),
+        [address],
+    )?;
+    if instructions.is_empty() {
+        return Ok(None);
+    }
+    let balance = connection.query_row(
+        "SELECT balance FROM accounts WHERE address = ?1",
+        [address],
+        |row| row.get(0),
+    ).optional()?;
+    Ok(Some(StakeAccount::new(address.clone(), balance, instructions)))
+}
+
+/// Loads the block with the given height. Blocks stored before their slot was tracked are treated
+/// as not existing since they can't be linked with the following blocks.
+fn load_block(connection: &Connection, height: u64) -> Result<Option<Block>> {
+    let block = connection.query_row(
+        "SELECT slot, parent_slot, hash, previous_blockhash, timestamp FROM blocks
+        WHERE height = ?1 AND slot IS NOT NULL",
+        [height],
+        |row| {
+            Ok(Block {
+                slot: row.get(0)?,
+                parent_slot: row.get::<_, Option<Slot>>(1)?.unwrap_or_default(),
+                height,
+                hash: row.get(2)?,
+                previous_blockhash: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                timestamp: row.get(4)?,
+                transactions: Vec::new(),
+                balance_changes: Vec::new(),
+                transaction_metas: Vec::new(),
+                token_transfers: Vec::new(),
+                token_balance_changes: Vec::new(),
+                raw_instructions: Vec::new(),
+                custom_instructions: Vec::new(),
+                stake_instructions: Vec::new(),
+                votes: Vec::new(),
+                rewards: Vec::new(),
+            })
+        },
+    ).optional()?;
+    let Some(mut block) = block else {
+        return Ok(None);
+    };
+    let mut statement = connection.prepare_cached(&format!(
+        "SELECT {} FROM transactions WHERE block_height = ?1 ORDER BY idx",
+        TRANSACTION_COLUMNS
+    ))?;
+    block.transactions = statement.query_map([height], system_transaction_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut statement = connection.prepare_cached(
+        "SELECT address, pre_balance, post_balance FROM balance_changes
+        WHERE block_height = ?1 ORDER BY idx"
+    )?;
+    block.balance_changes = statement.query_map([height], |row| {
+        Ok(BalanceChange {
+            address: row.get(0)?,
+            pre_balance: row.get(1)?,
+            post_balance: row.get(2)?,
+        })
+    })?.collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut statement = connection.prepare_cached(
+        "SELECT signature, fee, fee_payer, error, compute_units_consumed, memo FROM transaction_metas
+        WHERE block_height = ?1 ORDER BY idx"
+    )?;
+    block.transaction_metas = statement.query_map([height], |row| {
+        Ok(TransactionMeta {
+            signature: row.get(0)?,
+            fee: row.get(1)?,
+            fee_payer: row.get(2)?,
+            status: status_from_error(row.get(3)?),
+            compute_units_consumed: row.get(4)?,
+            memo: row.get(5)?,
+        })
+    })?.collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut statement = connection.prepare_cached(&format!(
+        "SELECT {} FROM token_transfers WHERE block_height = ?1 ORDER BY idx",
+        TOKEN_TRANSFER_COLUMNS
+    ))?;
+    block.token_transfers = statement.query_map([height], token_transfer_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut statement = connection.prepare_cached(
+        "SELECT account, owner, mint, decimals, pre_amount, post_amount FROM token_balance_changes
+        WHERE block_height = ?1 ORDER BY idx"
+    )?;
+    block.token_balance_changes = statement.query_map([height], token_balance_change_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut statement = connection.prepare_cached(&format!(
+        "SELECT {} FROM raw_instructions WHERE block_height = ?1 ORDER BY idx",
+        RAW_INSTRUCTION_COLUMNS
+    ))?;
+    block.raw_instructions = statement.query_map([height], raw_instruction_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut statement = connection.prepare_cached(&format!(
+        "SELECT {} FROM custom_instructions WHERE block_height = ?1 ORDER BY idx",
+        CUSTOM_INSTRUCTION_COLUMNS
+    ))?;
+    block.custom_instructions = statement.query_map([height], custom_instruction_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut statement = connection.prepare_cached(&format!(
+        "SELECT {} FROM stake_instructions WHERE block_height = ?1 ORDER BY idx",
+        STAKE_INSTRUCTION_COLUMNS
+    ))?;
+    block.stake_instructions = statement.query_map([height], stake_instruction_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut statement = connection.prepare_cached(&format!(
+        "SELECT {} FROM votes WHERE block_height = ?1 ORDER BY idx",
+        VOTE_COLUMNS
+    ))?;
+    block.votes = statement.query_map([height], vote_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut statement = connection.prepare_cached(&format!(
+        "SELECT {} FROM rewards WHERE block_height = ?1 ORDER BY idx",
+        REWARD_COLUMNS
+    ))?;
+    block.rewards = statement.query_map([height], reward_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(Some(block))
+}
+
+fn token_balance_change_from_row(row: &Row) -> rusqlite::Result<TokenBalanceChange> {
+    Ok(
+        TokenBalanceChange {
+            account: row.get(0)?,
+            owner: row.get(1)?,
+            mint: row.get(2)?,
+            decimals: row.get(3)?,
+            pre_amount: token_amount_from_row(row, 4)?,
+            post_amount: token_amount_from_row(row, 5)?,
+        }
+    )
+}
+
+/// Sets the token account of `change` to `amount`, or removes it if it doesn't exist.
+fn set_token_account(
+    transaction: &rusqlite::Transaction,
+    change: &TokenBalanceChange,
+    amount: Option<u64>,
+) -> Result<()> {
+    match amount {
+        Some(amount) => {
+            transaction.prepare_cached(
+                "INSERT INTO token_accounts (address, owner, mint, decimals, amount)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                ON CONFLICT(address) DO UPDATE SET
+                    owner = excluded.owner,
+                    mint = excluded.mint,
+                    decimals = excluded.decimals,
+                    amount = excluded.amount"
+            )?.execute(params![change.account, change.owner, change.mint, change.decimals, amount.to_string()])?;
+        }
+        None => {
+            transaction.prepare_cached("DELETE FROM token_accounts WHERE address = ?1")?
+                .execute([&change.account])?;
+        }
+    }
+    Ok(())
+}
+
+/// Restores the token accounts from before the first block above `slot`.
+fn revert_token_balance_changes(transaction: &rusqlite::Transaction, slot: Slot) -> Result<()> {
+    let removed = {
+        let mut statement = transaction.prepare_cached(
+            "SELECT c.account, c.owner, c.mint, c.decimals, c.pre_amount, c.post_amount
+            FROM token_balance_changes c
+            JOIN blocks b ON b.height = c.block_height
+            WHERE b.slot > ?1 ORDER BY c.block_height, c.idx"
+        )?;
+        let removed = statement.query_map([slot], token_balance_change_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        removed
+    };
+    for change in removed.iter().rev() {
+        set_token_account(transaction, change, change.pre_amount)?;
+    }
+    Ok(())
+}
+
+/// Applies the successful lamport movements and the rewards of `block` to the account balances.
+/// New accounts start with their balance before the block.
+fn apply_transfers(transaction: &rusqlite::Transaction, block: &Block) -> Result<()> {
+    let mut insert_account = transaction.prepare_cached(
+        "INSERT OR IGNORE INTO accounts (address, balance, key) VALUES (?1, ?2, ?3)"
+    )?;
+    let mut update_account = transaction.prepare_cached(
+        "UPDATE accounts SET balance = MAX(balance + ?2, 0) WHERE address = ?1"
+    )?;
+    let pre_balances = block.pre_balances();
+    for (sender, receiver, amount) in block.lamport_movements() {
+        for address in [receiver, sender] {
+            let pre_balance = pre_balances.get(address).copied().unwrap_or_default();
+            insert_account.execute(params![address, pre_balance, account_key(address)])?;
+        }
+        update_account.execute(params![receiver, amount as i64])?;
+        update_account.execute(params![sender, -(amount as i64)])?;
+    }
+    for reward in &block.rewards {
+        let pre_balance = pre_balances.get(&reward.address).copied().unwrap_or_default();
+        insert_account.execute(params![reward.address, pre_balance, account_key(&reward.address)])?;
+        update_account.execute(params![reward.address, reward.lamports])?;
+    }
+    Ok(())
+}
+
+/// Sets the account balances to the ones after `block`.
+fn apply_balance_changes(transaction: &rusqlite::Transaction, block: &Block) -> Result<()> {
+    let mut upsert_account = transaction.prepare_cached(
+        "INSERT INTO accounts (address, balance, key) VALUES (?1, ?2, ?3)
+        ON CONFLICT(address) DO UPDATE SET balance = excluded.balance"
+    )?;
+    for change in &block.balance_changes {
+        upsert_account.execute(params![change.address, change.post_balance, account_key(&change.address)])?;
+    }
+    Ok(())
+}
+
+/// Removes all blocks above `slot` together with their transactions and balance changes.
+fn delete_blocks(transaction: &rusqlite::Transaction, slot: Slot) -> Result<()> {
+    for table in [
+        "address_index",
+        "transactions",
+        "balance_changes",
+        "transaction_metas",
+        "token_transfers",
+        "token_balance_changes",
+        "raw_instructions",
+        "custom_instructions",
+        "stake_instructions",
+        "votes",
+        "rewards",
+    ] {
+        transaction.execute(
+            &format!(
+                "DELETE FROM {} WHERE block_height IN (SELECT height FROM blocks WHERE slot > ?1)",
+                table
+            ),
+            [slot],
+        )?;
+    }
+    transaction.execute("DELETE FROM blocks WHERE slot > ?1", [slot])?;
+    Ok(())
+}
+
+/// Removes all blocks above `slot` and reverts their lamport movements and rewards from the account
+/// balances.
+fn revert_transfers(transaction: &rusqlite::Transaction, slot: Slot) -> Result<()> {
+    // Ordered like `Block::lamport_movements`, system transactions before stake instructions.
+    let removed = {
+        let mut statement = transaction.prepare_cached(
+            &format!(
+                "SELECT sender, receiver, amount FROM (
+                    SELECT t.sender, t.receiver, t.amount, t.block_height, 0 AS source, t.idx FROM transactions t
+                    JOIN blocks b ON b.height = t.block_height
+                    WHERE b.slot > ?1 AND {0}
+                    UNION ALL
+                    SELECT t.stake_account, t.destination, t.amount, t.block_height, 1 AS source, t.idx
+                    FROM stake_instructions t
+                    JOIN blocks b ON b.height = t.block_height
+                    WHERE b.slot > ?1 AND t.destination IS NOT NULL AND {0}
+                ) ORDER BY block_height, source, idx",
+                SUCCESSFUL_TRANSACTION
+            )
+        )?;
+        let removed = statement.query_map([slot], |row| {
+            Ok((row.get::<_, Address>(0)?, row.get::<_, Address>(1)?, row.get::<_, i64>(2)?))
+        })?.collect::<rusqlite::Result<Vec<_>>>()?;
+        removed
+    };
+    // Stake accounts stay tracked as long as any of their instructions is stored.
+    let stake_accounts = {
+        let mut statement = transaction.prepare_cached(
+            "SELECT t.stake_account FROM stake_instructions t
+            JOIN blocks b ON b.height = t.block_height
+            WHERE b.slot > ?1"
+        )?;
+        let stake_accounts = statement.query_map([slot], |row| row.get::<_, Address>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        stake_accounts
+    };
+    let rewards = {
+        let mut statement = transaction.prepare_cached(
+            "SELECT r.address, r.lamports FROM rewards r
+            JOIN blocks b ON b.height = r.block_height
+            WHERE b.slot > ?1 ORDER BY r.block_height, r.idx"
+        )?;
+        let rewards = statement.query_map([slot], |row| {
+            Ok((row.get::<_, Address>(0)?, row.get::<_, i64>(1)?))
+        })?.collect::<rusqlite::Result<Vec<_>>>()?;
+        rewards
+    };
+    delete_blocks(transaction, slot)?;
+    let mut update_account = transaction.prepare_cached(
+        "UPDATE accounts SET balance = MAX(balance + ?2, 0) WHERE address = ?1"
+    )?;
+    let mut remove_account = transaction.prepare_cached(
+        "DELETE FROM accounts WHERE address = ?1
+        AND NOT EXISTS (SELECT 1 FROM address_index WHERE address = ?1)
+        AND NOT EXISTS (SELECT 1 FROM stake_instructions WHERE stake_account = ?1 OR destination = ?1)
+        AND NOT EXISTS (SELECT 1 FROM rewards WHERE address = ?1)"
+    )?;
+    // The rewards of a block are applied after its lamport movements, see `apply_transfers`.
+    for (address, lamports) in rewards.iter().rev() {
+        update_account.execute(params![address, -lamports])?;
+    }
+    for (sender, receiver, amount) in removed.iter().rev() {
+        update_account.execute(params![receiver, -amount])?;
+        update_account.execute(params![sender, amount])?;
+        remove_account.execute([receiver])?;
+        remove_account.execute([sender])?;
+    }
+    for address in stake_accounts {
+        remove_account.execute([address])?;
+    }
+    for (address, _) in rewards {
+        remove_account.execute([address])?;
+    }
+    Ok(())
+}
+
+/// Removes all blocks above `slot` and restores the account balances from before them.
+fn revert_balance_changes(transaction: &rusqlite::Transaction, slot: Slot) -> Result<()> {
+    let removed = {
+        let mut statement = transaction.prepare_cached(
+            "SELECT c.address, c.pre_balance FROM balance_changes c
+            JOIN blocks b ON b.height = c.block_height
+            WHERE b.slot > ?1 ORDER BY c.block_height, c.idx"
+        )?;
+        let removed = statement.query_map([slot], |row| {
+            Ok((row.get::<_, Address>(0)?, row.get::<_, u64>(1)?))
+        })?.collect::<rusqlite::Result<Vec<_>>>()?;
+        removed
+    };
+    delete_blocks(transaction, slot)?;
+    let mut restore_account = transaction.prepare_cached(
+        "UPDATE accounts SET balance = ?2 WHERE address = ?1"
+    )?;
+    let mut remove_account = transaction.prepare_cached(
+        "DELETE FROM accounts WHERE address = ?1"
+    )?;
+    for (address, pre_balance) in removed.iter().rev() {
+        // Accounts without balance didn't exist before the block.
+        if *pre_balance == 0 {
+            remove_account.execute([address])?;
+        } else {
+            restore_account.execute(params![address, pre_balance])?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug)]
+pub struct Sqlite {
+    connection: Arc<Mutex<Connection>>,
+    accounting: AccountingMode,
+}
+
+impl Sqlite {
+    pub fn open<P: AsRef<Path>>(path: P, accounting: AccountingMode) -> Result<Self> {
+        Self::with_connection(Connection::open(path)?, accounting)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory(accounting: AccountingMode) -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?, accounting)
+    }
+
+    fn with_connection(mut connection: Connection, accounting: AccountingMode) -> Result<Self> {
+        migrate(&mut connection)?;
+        Ok(Self {connection: Arc::new(Mutex::new(connection)), accounting})
+    }
+}
+
+impl Storage for Sqlite {
+    async fn add_block(&mut self, block: Block) -> Result<()> {
+        let mut connection = self.connection.lock().await;
+        block.day().ok_or_else(|| {
+            Error::InvalidBlock(block.height, format!("Invalid timestamp: {}", block.timestamp))
+        })?;
+        if let Some(address) = block.invalid_address() {
+            return Err(Error::InvalidBlock(block.height, format!("Invalid address: {}", address)));
+        }
+        let transaction = connection.transaction()?;
+        let exists: bool = transaction.query_row(
+            "SELECT EXISTS(SELECT 1 FROM blocks WHERE height = ?1)",
+            [block.height],
+            |row| row.get(0),
+        )?;
+        if exists {
+            return Err(Error::InvalidBlock(block.height, "Already exists in storage".to_string()));
+        }
+        let last_block: u64 = transaction.query_row(
+            "SELECT COALESCE(MAX(height), 0) FROM blocks",
+            [],
+            |row| row.get(0),
+        )?;
+        if block.height < last_block {
+            return Err(Error::InvalidBlock(
+                block.height,
+                format!("Block height must be ascending. last_block: {}", last_block))
+            );
+        }
+        transaction.execute(
+            "INSERT INTO blocks (height, slot, parent_slot, hash, previous_blockhash, timestamp)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                block.height,
+                block.slot,
+                block.parent_slot,
+                block.hash,
+                block.previous_blockhash,
+                block.timestamp,
+            ],
+        )?;
+        {
+            let mut insert_transaction = transaction.prepare_cached(&format!(
+                "INSERT INTO transactions (block_height, idx, {})
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                TRANSACTION_COLUMNS
+            ))?;
+            let mut insert_index = transaction.prepare_cached(
+                "INSERT OR IGNORE INTO address_index (address, block_height, idx) VALUES (?1, ?2, ?3)"
+            )?;
+            let mut insert_balance_change = transaction.prepare_cached(
+                "INSERT INTO balance_changes (block_height, idx, address, pre_balance, post_balance)
+                VALUES (?1, ?2, ?3, ?4, ?5)"
+            )?;
+            for (index, change) in block.balance_changes.iter().enumerate() {
+                insert_balance_change.execute(params![
+                    block.height,
+                    index,
+                    change.address,
+                    change.pre_balance,
+                    change.post_balance,
+                ])?;
+            }
+            let mut insert_transaction_meta = transaction.prepare_cached(
+                "INSERT INTO transaction_metas
+                    (block_height, idx, signature, fee, fee_payer, error, compute_units_consumed, memo)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"
+            )?;
+            for (index, meta) in block.transaction_metas.iter().enumerate() {
+                let error = match &meta.status {
+                    TransactionStatus::Success => None,
+                    TransactionStatus::Failed(error) => Some(error),
+                };
+                insert_transaction_meta.execute(params![
+                    block.height,
+                    index,
+                    meta.signature,
+                    meta.fee,
+                    meta.fee_payer,
+                    error,
+                    meta.compute_units_consumed,
+                    meta.memo,
+                ])?;
+            }
+            let mut insert_token_transfer = transaction.prepare_cached(&format!(
+                "INSERT INTO token_transfers (block_height, idx, {})
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                TOKEN_TRANSFER_COLUMNS
+            ))?;
+            for (index, transfer) in block.token_transfers.iter().enumerate() {
+                let (invoking_program, inner_index) = invocation_to_sql(&transfer.invocation);
+                insert_token_transfer.execute(params![
+                    block.height,
+                    index,
+                    transfer.signature,
+                    transfer.instruction_index,
+                    enum_to_sql(transfer.kind),
+                    transfer.mint,
+                    transfer.decimals,
+                    transfer.source,
+                    transfer.destination,
+                    transfer.amount.to_string(),
+                    invoking_program,
+                    inner_index,
+                ])?;
+            }
+            let mut insert_token_balance_change = transaction.prepare_cached(
+                "INSERT INTO token_balance_changes
+                    (block_height, idx, account, owner, mint, decimals, pre_amount, post_amount)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"
+            )?;
+            for (index, change) in block.token_balance_changes.iter().enumerate() {
+                insert_token_balance_change.execute(params![
+                    block.height,
+                    index,
+                    change.account,
+                    change.owner,
+                    change.mint,
+                    change.decimals,
+                    change.pre_amount.map(|amount| amount.to_string()),
+                    change.post_amount.map(|amount| amount.to_string()),
+                ])?;
+                set_token_account(&transaction, change, change.post_amount)?;
+            }
+            let mut insert_raw_instruction = transaction.prepare_cached(&format!(
+                "INSERT INTO raw_instructions (block_height, idx, {})
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                RAW_INSTRUCTION_COLUMNS
+            ))?;
+            for (index, instruction) in block.raw_instructions.iter().enumerate() {
+                let (invoking_program, inner_index) = invocation_to_sql(&instruction.invocation);
+                insert_raw_instruction.execute(params![
+                    block.height,
+                    index,
+                    instruction.signature,
+                    instruction.instruction_index,
+                    instruction.program_id,
+                    serde_json::to_string(&instruction.accounts).unwrap(),
+                    instruction.data,
+                    invoking_program,
+                    inner_index,
+                ])?;
+            }
+            let mut insert_custom_instruction = transaction.prepare_cached(&format!(
+                "INSERT INTO custom_instructions (block_height, idx, {})
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                CUSTOM_INSTRUCTION_COLUMNS
+            ))?;
+            for (index, instruction) in block.custom_instructions.iter().enumerate() {
+                let (invoking_program, inner_index) = invocation_to_sql(&instruction.invocation);
+                insert_custom_instruction.execute(params![
+                    block.height,
+                    index,
+                    instruction.signature,
+                    instruction.instruction_index,
+                    instruction.program_id,
+                    instruction.name,
+                    instruction.data.to_string(),
+                    invoking_program,
+                    inner_index,
+                ])?;
+            }
+            let mut insert_stake_instruction = transaction.prepare_cached(&format!(
+                "INSERT INTO stake_instructions (block_height, idx, {})
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                STAKE_INSTRUCTION_COLUMNS
+            ))?;
+            for (index, instruction) in block.stake_instructions.iter().enumerate() {
+                let (invoking_program, inner_index) = invocation_to_sql(&instruction.invocation);
+                insert_stake_instruction.execute(params![
+                    block.height,
+                    index,
+                    instruction.signature,
+                    instruction.instruction_index,
+                    enum_to_sql(instruction.kind),
+                    instruction.stake_account,
+                    instruction.vote_account,
+                    instruction.destination,
+                    instruction.amount,
+                    invoking_program,
+                    inner_index,
+                ])?;
+            }
+            let mut insert_vote = transaction.prepare_cached(&format!(
+                "INSERT INTO votes (block_height, idx, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                VOTE_COLUMNS
+            ))?;
+            for (index, vote) in block.votes.iter().enumerate() {
+                let (invoking_program, inner_index) = invocation_to_sql(&vote.invocation);
+                insert_vote.execute(params![
+                    block.height,
+                    index,
+                    vote.signature,
+                    vote.instruction_index,
+                    vote.vote_account,
+                    vote.authority,
+                    vote.slot,
+                    invoking_program,
+                    inner_index,
+                ])?;
+            }
+            let mut insert_reward = transaction.prepare_cached(&format!(
+                "INSERT INTO rewards (block_height, idx, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                REWARD_COLUMNS
+            ))?;
+            for (index, reward) in block.rewards.iter().enumerate() {
+                insert_reward.execute(params![
+                    block.height,
+                    index,
+                    reward.address,
+                    reward.lamports,
+                    reward.post_balance,
+                    reward.kind.map(enum_to_sql),
+                    reward.commission,
+                ])?;
+            }
+            for (index, tx) in block.transactions.iter().enumerate() {
+                let (invoking_program, inner_index) = invocation_to_sql(&tx.invocation);
+                insert_transaction.execute(params![
+                    block.height,
+                    index,
+                    tx.signature,
+                    tx.instruction_index,
+                    enum_to_sql(tx.kind),
+                    tx.sender,
+                    tx.receiver,
+                    tx.amount,
+                    invoking_program,
+                    inner_index,
+                ])?;
+                insert_index.execute(params![tx.sender, block.height, index])?;
+                insert_index.execute(params![tx.receiver, block.height, index])?;
+            }
+        }
+        match self.accounting {
+            AccountingMode::Transfers => apply_transfers(&transaction, &block)?,
+            AccountingMode::Balances => apply_balance_changes(&transaction, &block)?,
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    async fn get_accounts(&self, page: &Page<Address>) -> Result<Paginated<Account, Address>> {
+        let connection = self.connection.lock().await;
+        let (operator, order) = page_order(page);
+        let limit = page_limit(page);
+        // The accounts are ordered by their keys, so there is nothing after invalid cursors.
+        let cursor = match page.cursor.as_deref().map(parse_address) {
+            Some(Ok(cursor)) => Some(cursor.to_bytes()),
+            Some(Err(_)) => return Ok(Paginated {items: Vec::new(), next_cursor: None}),
+            None => None,
+        };
+        let mut params: Vec<&dyn ToSql> = Vec::from([&limit as &dyn ToSql]);
+        let mut condition = String::new();
+        if let Some(cursor) = &cursor {
+            condition = format!("WHERE key {} ?2", operator);
+            params.push(cursor);
+        }
+        let mut statement = connection.prepare_cached(&format!(
+            "SELECT address, balance FROM accounts {} ORDER BY key {} LIMIT ?1",
+            condition,
+            order
+        ))?;
+        let accounts = statement.query_map(params.as_slice(), |row| {
+            Ok(Account {address: row.get(0)?, balance: row.get(1)?})
+        })?.collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(Paginated::collect(accounts.into_iter(), page.limit, |account| account.address.clone()))
+    }
+
+    async fn get_account(&self, address: &Address) -> Result<Option<AccountSummary>> {
+        // Accounts stored before the addresses were validated are not served, like with the memory storage.
+        if parse_address(address).is_err() {
+            return Ok(None);
+        }
+        let connection = self.connection.lock().await;
+        let balance = connection.query_row(
+            "SELECT balance FROM accounts WHERE address = ?1",
+            [address],
+            |row| row.get::<_, u64>(0),
+        ).optional()?;
+        // Only walks the transactions of the address via the address index.
+        let summary = connection.query_row(
+            &format!(
+                "SELECT COUNT(*), MIN(a.block_height), MAX(a.block_height),
+                    COALESCE(SUM(CASE WHEN t.sender = ?1 AND {0} THEN t.amount END), 0),
+                    COALESCE(SUM(CASE WHEN t.receiver = ?1 AND {0} THEN t.amount END), 0),
+                    COUNT(DISTINCT NULLIF(CASE WHEN t.sender = ?1 THEN t.receiver ELSE t.sender END, ?1))
+                FROM address_index a
+                JOIN transactions t ON t.block_height = a.block_height AND t.idx = a.idx
+                WHERE a.address = ?1",
+                SUCCESSFUL_TRANSACTION
+            ),
+            [address],
+            |row| {
+                Ok(AccountSummary {
+                    address: address.clone(),
+                    balance,
+                    first_seen_block: row.get(1)?,
+                    last_seen_block: row.get(2)?,
+                    total_sent: row.get(3)?,
+                    total_received: row.get(4)?,
+                    transaction_count: row.get(0)?,
+                    counterparties: row.get(5)?,
+                })
+            },
+        )?;
+        if summary.balance.is_none() && summary.transaction_count == 0 {
+            return Ok(None);
+        }
+        Ok(Some(summary))
+    }
+
+    async fn get_transactions(
+        &self,
+        address: &Address,
+        filter: &TransactionFilter,
+        page: &Page<TransactionCursor>,
+    ) -> Result<Paginated<TransactionWithMeta, TransactionCursor>> {
+        let connection = self.connection.lock().await;
+        let (operator, order) = page_order(page);
+        let limit = page_limit(page);
+        let kind = filter.kind.map(enum_to_sql);
+        let mut params: Vec<&dyn ToSql> = Vec::from([address as &dyn ToSql, &limit]);
+        let mut conditions = Vec::new();
+        if let Some(cursor) = &page.cursor {
+            conditions.push(format!("(a.block_height, a.idx) {} (?3, ?4)", operator));
+            params.push(&cursor.block_height);
+            params.push(&cursor.index);
+        }
+        let bounds = [
+            (filter.from.as_ref().map(|from| from as &dyn ToSql), "b.timestamp >="),
+            (filter.to.as_ref().map(|to| to as &dyn ToSql), "b.timestamp <="),
+            (filter.min_amount.as_ref().map(|amount| amount as &dyn ToSql), "t.amount >="),
+            (filter.max_amount.as_ref().map(|amount| amount as &dyn ToSql), "t.amount <="),
+        ];
+        for (bound, condition) in bounds {
+            if let Some(bound) = bound {
+                params.push(bound);
+                conditions.push(format!("{} ?{}", condition, params.len()));
+            }
+        }
+        match filter.direction {
+            Some(Direction::In) => conditions.push("t.receiver = ?1 AND t.sender != ?1".to_string()),
+            Some(Direction::Out) => conditions.push("t.sender = ?1 AND t.receiver != ?1".to_string()),
+            Some(Direction::SelfTransfer) => conditions.push("t.sender = ?1 AND t.receiver = ?1".to_string()),
+            None => {}
+        }
+        // Transactions without metadata have an unknown status and never match.
+        match filter.status {
+            Some(StatusFilter::Success) => conditions.push("m.signature IS NOT NULL AND m.error IS NULL".to_string()),
+            Some(StatusFilter::Failed) => conditions.push("m.error IS NOT NULL".to_string()),
+            None => {}
+        }
+        if let Some(kind) = &kind {
+            params.push(kind);
+            conditions.push(format!("t.kind = ?{}", params.len()));
+        }
+        let condition: String = conditions.iter().map(|condition| format!(" AND {}", condition)).collect();
+        let rows = {
+            let mut statement = connection.prepare_cached(&format!(
+                "{} JOIN address_index a ON a.block_height = t.block_height AND a.idx = t.idx
+                WHERE a.address = ?1{}
+                ORDER BY a.block_height {2}, a.idx {2} LIMIT ?2",
+                SELECT_TRANSACTIONS,
+                condition,
+                order
+            ))?;
+            let rows = statement.query_map(params.as_slice(), |row| {
+                let cursor = TransactionCursor {block_height: row.get(14)?, index: row.get(15)?};
+                Ok((transaction_from_row(row)?, cursor))
+            })?.collect::<rusqlite::Result<Vec<_>>>()?;
+            rows
+        };
+        let rows = Paginated::collect(rows.into_iter(), page.limit, |(_, cursor)| *cursor);
+        let transactions = rows.items.into_iter().map(|(transaction, _)| transaction).collect();
+        Ok(Paginated {items: transactions, next_cursor: rows.next_cursor})
+    }
+
+    async fn get_transactions_by_signature(&self, signature: &Signature) -> Result<Vec<TransactionWithMeta>> {
+        let connection = self.connection.lock().await;
+        query_transactions(
+            &connection,
+            &format!("{} WHERE t.signature = ?1 ORDER BY t.block_height, t.idx", SELECT_TRANSACTIONS),
+            [signature],
+        )
+    }
+
+    async fn get_transactions_by_day(&self, day: &NaiveDate) -> Result<Vec<TransactionWithMeta>> {
+        let connection = self.connection.lock().await;
+        let start = day.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
+        let end = start + 24 * 60 * 60;
+        query_transactions(
+            &connection,
+            &format!(
+                "{} WHERE b.timestamp >= ?1 AND b.timestamp < ?2 ORDER BY t.block_height, t.idx",
+                SELECT_TRANSACTIONS
+            ),
+            [start, end],
+        )
+    }
+
+    async fn get_transactions_by_memo(&self, filter: &MemoFilter) -> Result<Vec<TransactionWithMeta>> {
+        let connection = self.connection.lock().await;
+        let (condition, memo) = match filter {
+            MemoFilter::Exact(memo) => ("m.memo = ?1", memo),
+            // No UTF-8 string contains the byte 0xff, so the range covers exactly the memos starting
+            // with the prefix and can use the index.
+            MemoFilter::Prefix(prefix) => ("m.memo >= ?1 AND m.memo < ?1 || x'ff'", prefix),
+        };
+        query_transactions(
+            &connection,
+            &format!("{} WHERE {} ORDER BY t.block_height, t.idx", SELECT_TRANSACTIONS, condition),
+            [memo],
+        )
+    }
+
+    async fn get_token_balances(&self, owner: &Address) -> Result<Vec<TokenBalance>> {
+        let connection = self.connection.lock().await;
+        let mut statement = connection.prepare_cached(
+            "SELECT mint, decimals, amount FROM token_accounts WHERE owner = ?1"
+        )?;
+        let accounts = statement.query_map([owner], |row| {
+            Ok(TokenBalance {
+                mint: row.get(0)?,
+                decimals: row.get(1)?,
+                amount: token_amount_from_row(row, 2)?.unwrap_or_default(),
+            })
+        })?.collect::<rusqlite::Result<Vec<_>>>()?;
+        let mut balances: BTreeMap<Address, TokenBalance> = BTreeMap::new();
+        for account in accounts {
+            match balances.get_mut(&account.mint) {
+                Some(balance) => balance.amount = balance.amount.saturating_add(account.amount),
+                None => {
+                    balances.insert(account.mint.clone(), account);
+                }
+            }
+        }
+        Ok(balances.into_values().collect())
+    }
+
+    async fn get_token_transfers_by_mint(&self, mint: &Address) -> Result<Vec<TransactionWithMeta<TokenTransfer>>> {
+        let connection = self.connection.lock().await;
+        query_token_transfers(
+            &connection,
+            &format!("{} WHERE t.mint = ?1 ORDER BY t.block_height, t.idx", SELECT_TOKEN_TRANSFERS),
+            [mint],
+        )
+    }
+
+    async fn get_instructions_by_program(
+        &self,
+        program_id: &Address,
+    ) -> Result<Vec<TransactionWithMeta<RawInstruction>>> {
+        let connection = self.connection.lock().await;
+        query_raw_instructions(
+            &connection,
+            &format!("{} WHERE t.program_id = ?1 ORDER BY t.block_height, t.idx", SELECT_RAW_INSTRUCTIONS),
+            [program_id],
+        )
+    }
+
+    async fn get_custom_instructions_by_program(
+        &self,
+        program_id: &Address,
+    ) -> Result<Vec<TransactionWithMeta<CustomInstruction>>> {
+        let connection = self.connection.lock().await;
+        query_custom_instructions(
+            &connection,
+            &format!("{} WHERE t.program_id = ?1 ORDER BY t.block_height, t.idx", SELECT_CUSTOM_INSTRUCTIONS),
+            [program_id],
+        )
+    }
+
+    async fn get_stake_account(&self, address: &Address) -> Result<Option<StakeAccount>> {
+        let connection = self.connection.lock().await;
+        load_stake_account(&connection, address)
+    }
+
+    async fn get_validator(&self, vote_account: &Address) -> Result<Option<Validator>> {
+        let connection = self.connection.lock().await;
+        let (votes, last_voted_slot, last_vote_timestamp) = connection.query_row(
+            &format!(
+                "SELECT COUNT(*), MAX(t.slot), MAX(b.timestamp) FROM votes t
+                JOIN blocks b ON b.height = t.block_height
+                WHERE t.vote_account = ?1 AND {}",
+                SUCCESSFUL_TRANSACTION
+            ),
+            [vote_account],
+            |row| Ok((row.get::<_, u64>(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        let delegated = {
+            let mut statement = connection.prepare_cached(
+                "SELECT DISTINCT stake_account FROM stake_instructions
+                WHERE vote_account = ?1 AND kind = 'delegate' ORDER BY stake_account"
+            )?;
+            let delegated = statement.query_map([vote_account], |row| row.get::<_, Address>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            delegated
+        };
+        let mut stake_accounts = Vec::new();
+        for address in delegated {
+            let Some(stake_account) = load_stake_account(&connection, &address)? else {
+                continue;
+            };
+            if stake_account.vote_account.as_ref() == Some(vote_account) && !stake_account.deactivated {
+                stake_accounts.push(stake_account.address);
+            }
+        }
+        if votes == 0 && stake_accounts.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Validator {
+            vote_account: vote_account.clone(),
+            votes,
+            last_voted_slot,
+            last_vote_timestamp,
+            stake_accounts,
+        }))
+    }
+
+    async fn get_rewards(&self, address: &Address) -> Result<Vec<BlockReward>> {
+        let connection = self.connection.lock().await;
+        let mut statement = connection.prepare_cached(
+            "SELECT r.address, r.lamports, r.post_balance, r.kind, r.commission, b.slot, b.timestamp FROM rewards r
+            JOIN blocks b ON b.height = r.block_height
+            WHERE r.address = ?1 ORDER BY r.block_height, r.idx"
+        )?;
+        let rewards = statement.query_map([address], |row| {
+            Ok(BlockReward {slot: row.get(5)?, timestamp: row.get(6)?, reward: reward_from_row(row)?})
+        })?.collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rewards)
+    }
+
+    async fn get_block_rewards(&self, height: u64) -> Result<Option<Vec<Reward>>> {
+        let connection = self.connection.lock().await;
+        let exists = connection.query_row(
+            "SELECT 1 FROM blocks WHERE height = ?1",
+            [height],
+            |_| Ok(()),
+        ).optional()?;
+        if exists.is_none() {
+            return Ok(None);
+        }
+        let mut statement = connection.prepare_cached(&format!(
+            "SELECT {} FROM rewards WHERE block_height = ?1 ORDER BY idx",
+            REWARD_COLUMNS
+        ))?;
+        let rewards = statement.query_map([height], reward_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(Some(rewards))
+    }
+
+    async fn get_last_slot(&self) -> Result<Option<Slot>> {
+        let connection = self.connection.lock().await;
+        let slot = connection.query_row(
+            "SELECT slot FROM blocks ORDER BY height DESC LIMIT 1",
+            [],
+            |row| row.get::<_, Option<Slot>>(0),
+        ).optional()?;
+        Ok(slot.flatten())
+    }
+
+    async fn get_last_block(&self) -> Result<Option<Block>> {
+        let connection = self.connection.lock().await;
+        let height = connection.query_row(
+            "SELECT MAX(height) FROM blocks",
+            [],
+            |row| row.get::<_, Option<u64>>(0),
+        )?;
+        match height {
+            Some(height) => load_block(&connection, height),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_blocks(&self, from: u64, to: u64) -> Result<Vec<Block>> {
+        let connection = self.connection.lock().await;
+        let heights = {
+            let mut statement = connection.prepare_cached(
+                "SELECT height FROM blocks WHERE height BETWEEN ?1 AND ?2 ORDER BY height"
+            )?;
+            // SQLite integers are signed.
+            let heights = statement.query_map([from, to.min(i64::MAX as u64)], |row| row.get::<_, u64>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            heights
+        };
+        let mut blocks = Vec::with_capacity(heights.len());
+        for height in heights {
+            blocks.extend(load_block(&connection, height)?);
+        }
+        Ok(blocks)
+    }
+
+    async fn rollback_to(&mut self, slot: Slot) -> Result<()> {
+        let mut connection = self.connection.lock().await;
+        let transaction = connection.transaction()?;
+        revert_token_balance_changes(&transaction, slot)?;
+        match self.accounting {
+            AccountingMode::Transfers => revert_transfers(&transaction, slot)?,
+            AccountingMode::Balances => revert_balance_changes(&transaction, slot)?,
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::tests;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add_and_fetch_data() {
+        tests::add_and_fetch_data(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_add_block_failures() {
+        tests::add_block_failures(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_transactions_by_day() {
+        tests::transactions_by_day(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_last_slot() {
+        tests::last_slot(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_last_block() {
+        tests::last_block(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_blocks() {
+        tests::blocks(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_account_summary() {
+        tests::account_summary(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_pagination() {
+        tests::pagination(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_account_order() {
+        tests::account_order(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_transaction_filter() {
+        tests::transaction_filter(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_rollback() {
+        tests::rollback(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_initial_balances() {
+        tests::initial_balances(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_balance_accounting() {
+        tests::balance_accounting(&mut Sqlite::open_in_memory(AccountingMode::Balances).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_transaction_meta() {
+        tests::transaction_meta(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_token_balances() {
+        tests::token_balances(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_raw_instructions() {
+        tests::raw_instructions(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_custom_instructions() {
+        tests::custom_instructions(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_stake_and_votes() {
+        tests::stake_and_votes(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_rewards() {
+        tests::rewards(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_transactions_by_memo() {
+        tests::transactions_by_memo(&mut Sqlite::open_in_memory(AccountingMode::Transfers).unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_reopen() {
+        let directory = std::env::temp_dir().join(format!("solana_aggregator_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("test_reopen.sqlite");
+        let _ = std::fs::remove_file(&path);
+        tests::add_and_fetch_data(&mut Sqlite::open(&path, AccountingMode::Transfers).unwrap()).await;
+        // Everything must still be there after reopening the database, including the migrations
+        // not being applied again.
+        let sqlite = Sqlite::open(&path, AccountingMode::Transfers).unwrap();
+        assert_eq!(sqlite.get_accounts(&Page::default()).await.unwrap().items.len(), 2);
+        assert_eq!(tests::address_transactions(&sqlite, &tests::address("1")).await.len(), 4);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_fill_account_keys() {
+        let connection = Connection::open_in_memory().unwrap();
+        let version = MIGRATIONS.iter().position(|migration| migration.contains("accounts ADD COLUMN key")).unwrap();
+        for migration in &MIGRATIONS[..version] {
+            connection.execute_batch(migration).unwrap();
+        }
+        connection.pragma_update(None, "user_version", version).unwrap();
+        // The base58 string of the first key sorts behind the one of the second.
+        let addresses: Vec<Address> = [[9; 32], [200; 32]].into_iter()
+            .map(|bytes| solana_sdk::pubkey::Pubkey::new_from_array(bytes).to_string())
+            .collect();
+        for address in &addresses {
+            connection.execute("INSERT INTO accounts (address, balance) VALUES (?1, 0)", [address]).unwrap();
+        }
+        let sqlite = Sqlite::with_connection(connection, AccountingMode::Transfers).unwrap();
+        let accounts = sqlite.get_accounts(&Page::default()).await.unwrap().items;
+        assert_eq!(accounts.into_iter().map(|account| account.address).collect::<Vec<_>>(), addresses);
+    }
+}
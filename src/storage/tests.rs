@@ -0,0 +1,1273 @@
+use crate::error::Error;
+use crate::storage::{Direction, MemoFilter, Order, Page, StatusFilter, Storage, TransactionCursor, TransactionFilter};
+use crate::types::{
+    Account, AccountSummary, Address, BalanceChange, Block, BlockReward, CustomInstruction, Invocation,
+    RawInstruction, Reward, RewardKind, Signature, StakeInstruction, StakeInstructionKind, SystemInstructionKind,
+    TokenBalance, TokenBalanceChange, TokenTransfer, TokenTransferKind, Transaction, TransactionMeta,
+    TransactionStatus, TransactionWithMeta, Validator, Vote,
+};
+use chrono::NaiveDate;
+use solana_sdk::pubkey::Pubkey;
+
+async fn assert_accounts<S: Storage>(storage: &S, expected_accounts: &[Account]) {
+    let accounts = storage.get_accounts(&Page::default()).await.unwrap().items;
+    assert_eq!(accounts.len(), expected_accounts.len());
+    for account in expected_accounts {
+        assert!(accounts.contains(account));
+    }
+}
+/// All transactions of `address` in a single page.
+pub async fn address_transactions<S: Storage>(storage: &S, address: &Address) -> Vec<TransactionWithMeta> {
+    storage.get_transactions(address, &TransactionFilter::default(), &Page::default()).await.unwrap().items
+}
+async fn assert_transactions<S: Storage>(
+    storage: &S,
+    address: &Address,
+    expected_transactions: Vec<TransactionWithMeta>
+) {
+    let transactions = address_transactions(storage, address).await;
+    assert_eq!(transactions.len(), expected_transactions.len());
+    for transaction in expected_transactions.clone() {
+        assert!(transactions.contains(&transaction));
+    }
+}
+async fn assert_transactions_by_signature<S: Storage>(
+    storage: &S,
+    signature: &Signature,
+    expected_transactions: Vec<TransactionWithMeta>
+) {
+    let transactions = storage.get_transactions_by_signature(signature).await.unwrap();
+    assert_eq!(transactions, expected_transactions);
+}
+
+/// A valid address for the short `name`s used in the tests, which keeps their order.
+pub fn address(name: &str) -> Address {
+    let mut bytes = [0; 32];
+    bytes[..name.len()].copy_from_slice(name.as_bytes());
+    Pubkey::new_from_array(bytes).to_string()
+}
+
+fn get_block(height: u64, transactions: Vec<Transaction>) -> Block {
+    Block {
+        slot: height * 2,
+        parent_slot: height.saturating_sub(1) * 2,
+        height,
+        hash: height.to_string(),
+        previous_blockhash: height.saturating_sub(1).to_string(),
+        timestamp: height as i64,
+        transactions,
+        balance_changes: Vec::new(),
+        transaction_metas: Vec::new(),
+        token_transfers: Vec::new(),
+        token_balance_changes: Vec::new(),
+        raw_instructions: Vec::new(),
+        custom_instructions: Vec::new(),
+        stake_instructions: Vec::new(),
+        votes: Vec::new(),
+        rewards: Vec::new(),
+    }
+}
+
+fn with_pre_balances(mut block: Block, pre_balances: &[(&str, u64)]) -> Block {
+    for (name, balance) in pre_balances {
+        block.balance_changes.push(
+            BalanceChange {
+                address: address(name),
+                pre_balance: *balance,
+                post_balance: *balance,
+            }
+        );
+    }
+    block
+}
+
+pub async fn add_and_fetch_data<S: Storage>(storage: &mut S) {
+    assert_eq!(storage.get_accounts(&Page::default()).await.unwrap().items.len(), 0);
+
+    let account_0 = Account {
+        address: address("0"),
+        balance: 0,
+    };
+    let account_1 = Account {
+        address: address("1"),
+        balance: 0,
+    };
+
+    let tx_0 = TransactionWithMeta {
+        data: Transaction {
+            signature: "a".to_string(),
+            instruction_index: 0,
+            invocation: None,
+            kind: SystemInstructionKind::Transfer,
+            sender: account_0.address.clone(),
+            receiver: account_1.address.clone(),
+            amount: 1,
+        },
+        timestamp: 0,
+        meta: None,
+    };
+    let tx_1 = TransactionWithMeta {
+        data: Transaction {
+            signature: "b".to_string(),
+            instruction_index: 0,
+            invocation: None,
+            kind: SystemInstructionKind::Transfer,
+            sender: account_1.address.clone(),
+            receiver: account_0.address.clone(),
+            amount: 2,
+        },
+        timestamp: 1,
+        meta: None,
+    };
+    let tx_2 = TransactionWithMeta {
+        data: Transaction {
+            signature: "c".to_string(),
+            instruction_index: 0,
+            invocation: None,
+            kind: SystemInstructionKind::Transfer,
+            sender: account_1.address.clone(),
+            receiver: account_1.address.clone(),
+            amount: 5,
+        },
+        timestamp: 2,
+        meta: None,
+    };
+    let tx_3 = TransactionWithMeta {
+        data: Transaction {
+            signature: "c".to_string(),
+            instruction_index: 1,
+            invocation: None,
+            kind: SystemInstructionKind::Transfer,
+            sender: account_1.address.clone(),
+            receiver: account_0.address.clone(),
+            amount: 10,
+        },
+        timestamp: 2,
+        meta: None,
+    };
+
+    let block_0 = with_pre_balances(
+        get_block(0, Vec::from([tx_0.data.clone()])),
+        &[("0", 100), ("1", 100)]
+    );
+    let block_1 = get_block(1, Vec::from([tx_1.data.clone()]));
+    let block_2 = get_block(2, Vec::from([tx_2.data.clone(), tx_3.data.clone()]));
+
+    let mut expected_accounts = Vec::from([account_0, account_1]);
+
+    assert!(storage.add_block(block_0).await.is_ok());
+    expected_accounts[0].balance = 99;
+    expected_accounts[1].balance = 101;
+    assert_accounts(storage, &expected_accounts).await;
+    assert_transactions(storage, &expected_accounts[0].address, Vec::from([tx_0.clone()])).await;
+    assert_transactions(storage, &expected_accounts[1].address, Vec::from([tx_0.clone()])).await;
+
+    assert!(storage.add_block(block_1).await.is_ok());
+    expected_accounts[0].balance = 101;
+    expected_accounts[1].balance = 99;
+    assert_accounts(storage, &expected_accounts).await;
+    assert_transactions(storage, &expected_accounts[0].address, Vec::from([tx_0.clone(), tx_1.clone()])).await;
+    assert_transactions(storage, &expected_accounts[1].address, Vec::from([tx_0.clone(), tx_1.clone()])).await;
+
+    assert!(storage.add_block(block_2).await.is_ok());
+    expected_accounts[0].balance = 111;
+    expected_accounts[1].balance = 89;
+    assert_accounts(storage, &expected_accounts).await;
+    assert_transactions(
+        storage,
+        &expected_accounts[0].address,
+        Vec::from([tx_0.clone(),tx_1.clone(), tx_3.clone()])
+    ).await;
+    assert_transactions(
+        storage,
+        &expected_accounts[1].address,
+        Vec::from([tx_0.clone(), tx_1.clone(), tx_2.clone(), tx_3.clone()])
+    ).await;
+    assert_transactions_by_signature(storage, &"a".to_string(), Vec::from([tx_0.clone()])).await;
+    assert_transactions_by_signature(storage, &"b".to_string(), Vec::from([tx_1.clone()])).await;
+    assert_transactions_by_signature(
+        storage,
+        &"c".to_string(),
+        Vec::from([tx_2.clone(), tx_3.clone()])
+    ).await;
+    assert_transactions_by_signature(storage, &"d".to_string(), Vec::new()).await;
+}
+
+pub async fn add_block_failures<S: Storage>(storage: &mut S) {
+    let block_0 = get_block(0, Vec::new());
+    let block_1 = get_block(1, Vec::new());
+
+    assert!(storage.add_block(block_1.clone()).await.is_ok());
+    // Adding the block again should lead to failure
+    match storage.add_block(block_1).await {
+        Err(Error::InvalidBlock(height, error)) => {
+            assert_eq!(height, 1);
+            assert_eq!(error, "Already exists in storage");
+        }
+        _ => {panic!("existing block test failed")}
+    }
+    match storage.add_block(block_0).await {
+        Err(Error::InvalidBlock(height, error)) => {
+            assert_eq!(height, 0);
+            assert_eq!(error, "Block height must be ascending. last_block: 1");
+        }
+        _ => {panic!("lower block height test failed")}
+    }
+    let mut block_2 = get_block(2, Vec::new());
+    block_2.rewards = Vec::from([
+        Reward {address: "0OIl".to_string(), lamports: 1, post_balance: 1, kind: None, commission: None},
+    ]);
+    match storage.add_block(block_2).await {
+        Err(Error::InvalidBlock(height, error)) => {
+            assert_eq!(height, 2);
+            assert_eq!(error, "Invalid address: 0OIl");
+        }
+        _ => {panic!("invalid address test failed")}
+    }
+    assert_eq!(storage.get_last_block().await.unwrap().map(|block| block.height), Some(1));
+}
+
+pub async fn transactions_by_day<S: Storage>(storage: &mut S) {
+    // 23/05/2023 00:00:00 UTC
+    let day_start = 1684800000;
+
+    let transaction = |signature: &str| Transaction {
+        signature: signature.to_string(),
+        instruction_index: 0,
+        invocation: None,
+        kind: SystemInstructionKind::Transfer,
+        sender: address("0"),
+        receiver: address("1"),
+        amount: 1,
+    };
+    let mut block_0 = get_block(0, Vec::from([transaction("a")]));
+    block_0.timestamp = day_start - 1;
+    let mut block_1 = get_block(1, Vec::from([transaction("b")]));
+    block_1.timestamp = day_start;
+    let mut block_2 = get_block(2, Vec::from([transaction("c"), transaction("d")]));
+    block_2.timestamp = day_start + 86399;
+    let mut block_3 = get_block(3, Vec::from([transaction("e")]));
+    block_3.timestamp = day_start + 86400;
+
+    for block in [block_0, block_1, block_2, block_3] {
+        assert!(storage.add_block(block).await.is_ok());
+    }
+
+    let signatures = |transactions: Vec<TransactionWithMeta>| -> Vec<Signature> {
+        transactions.into_iter().map(|transaction| transaction.data.signature).collect()
+    };
+    let day = |day: u32| NaiveDate::from_ymd_opt(2023, 5, day).unwrap();
+    assert_eq!(signatures(storage.get_transactions_by_day(&day(22)).await.unwrap()), ["a"]);
+    assert_eq!(signatures(storage.get_transactions_by_day(&day(23)).await.unwrap()), ["b", "c", "d"]);
+    assert_eq!(signatures(storage.get_transactions_by_day(&day(24)).await.unwrap()), ["e"]);
+    assert!(storage.get_transactions_by_day(&day(25)).await.unwrap().is_empty());
+}
+
+pub async fn last_slot<S: Storage>(storage: &mut S) {
+    assert_eq!(storage.get_last_slot().await.unwrap(), None);
+    assert!(storage.add_block(get_block(1, Vec::new())).await.is_ok());
+    assert_eq!(storage.get_last_slot().await.unwrap(), Some(2));
+    assert!(storage.add_block(get_block(2, Vec::new())).await.is_ok());
+    assert_eq!(storage.get_last_slot().await.unwrap(), Some(4));
+}
+
+pub async fn last_block<S: Storage>(storage: &mut S) {
+    assert_eq!(storage.get_last_block().await.unwrap(), None);
+    let transaction = Transaction {
+        signature: "a".to_string(),
+        instruction_index: 1,
+        invocation: None,
+        kind: SystemInstructionKind::Transfer,
+        sender: address("0"),
+        receiver: address("1"),
+        amount: 1,
+    };
+    let invocation = Invocation {program_id: address("p"), inner_index: 3};
+    let assign = Transaction {
+        invocation: Some(invocation),
+        kind: SystemInstructionKind::Assign,
+        amount: 0,
+        ..transaction.clone()
+    };
+    let block_1 = get_block(1, Vec::from([transaction, assign]));
+    let block_2 = get_block(2, Vec::new());
+    assert!(storage.add_block(block_1.clone()).await.is_ok());
+    assert_eq!(storage.get_last_block().await.unwrap(), Some(block_1));
+    assert!(storage.add_block(block_2.clone()).await.is_ok());
+    assert_eq!(storage.get_last_block().await.unwrap(), Some(block_2));
+}
+
+pub async fn blocks<S: Storage>(storage: &mut S) {
+    assert!(storage.get_blocks(0, u64::MAX).await.unwrap().is_empty());
+    let blocks = [get_block(1, Vec::new()), get_block(2, Vec::new()), get_block(4, Vec::new())];
+    for block in blocks.clone() {
+        assert!(storage.add_block(block).await.is_ok());
+    }
+    assert_eq!(storage.get_blocks(1, 4).await.unwrap(), blocks);
+    assert_eq!(storage.get_blocks(0, u64::MAX).await.unwrap(), blocks);
+    assert_eq!(storage.get_blocks(2, 3).await.unwrap(), blocks[1..2]);
+    assert_eq!(storage.get_blocks(4, 4).await.unwrap(), blocks[2..]);
+    assert!(storage.get_blocks(3, 3).await.unwrap().is_empty());
+    assert!(storage.get_blocks(5, 10).await.unwrap().is_empty());
+    assert!(storage.get_blocks(4, 1).await.unwrap().is_empty());
+
+    assert!(storage.rollback_to(blocks[1].slot).await.is_ok());
+    assert_eq!(storage.get_blocks(0, u64::MAX).await.unwrap(), blocks[..2]);
+}
+
+pub async fn account_summary<S: Storage>(storage: &mut S) {
+    let transaction = |signature: &str, kind, sender: &str, receiver: &str, amount| Transaction {
+        signature: signature.to_string(),
+        instruction_index: 0,
+        invocation: None,
+        kind,
+        sender: address(sender),
+        receiver: address(receiver),
+        amount,
+    };
+    let mut block_1 = with_pre_balances(
+        get_block(1, Vec::from([
+            transaction("a", SystemInstructionKind::Transfer, "0", "1", 10),
+            transaction("b", SystemInstructionKind::Transfer, "0", "2", 5),
+            transaction("c", SystemInstructionKind::Assign, "0", "0", 0),
+        ])),
+        &[("0", 100), ("1", 0), ("2", 0)]
+    );
+    block_1.transaction_metas = Vec::from([
+        TransactionMeta {
+            signature: "b".to_string(),
+            fee: 5000,
+            fee_payer: address("0"),
+            status: TransactionStatus::Failed("InstructionError".to_string()),
+            compute_units_consumed: None,
+            memo: None,
+        },
+    ]);
+    let block_2 = get_block(2, Vec::from([transaction("d", SystemInstructionKind::Transfer, "1", "0", 3)]));
+    for block in [block_1.clone(), block_2.clone()] {
+        assert!(storage.add_block(block).await.is_ok());
+    }
+    let summary_0 = AccountSummary {
+        address: address("0"),
+        balance: Some(93),
+        first_seen_block: Some(1),
+        last_seen_block: Some(2),
+        total_sent: 10,
+        total_received: 3,
+        transaction_count: 4,
+        counterparties: 2,
+    };
+    assert_eq!(storage.get_account(&address("0")).await.unwrap(), Some(summary_0.clone()));
+    let summary_1 = AccountSummary {
+        address: address("1"),
+        balance: Some(7),
+        first_seen_block: Some(1),
+        last_seen_block: Some(2),
+        total_sent: 3,
+        total_received: 10,
+        transaction_count: 2,
+        counterparties: 1,
+    };
+    assert_eq!(storage.get_account(&address("1")).await.unwrap(), Some(summary_1.clone()));
+    // Failed transactions are counted but not summed up.
+    assert_eq!(
+        storage.get_account(&address("2")).await.unwrap(),
+        Some(AccountSummary {
+            address: address("2"),
+            balance: None,
+            first_seen_block: Some(1),
+            last_seen_block: Some(1),
+            total_sent: 0,
+            total_received: 0,
+            transaction_count: 1,
+            counterparties: 1,
+        })
+    );
+    assert_eq!(storage.get_account(&address("3")).await.unwrap(), None);
+    assert_eq!(storage.get_account(&"0OIl".to_string()).await.unwrap(), None);
+
+    assert!(storage.rollback_to(block_1.slot).await.is_ok());
+    assert_eq!(
+        storage.get_account(&address("0")).await.unwrap(),
+        Some(AccountSummary {
+            balance: Some(90),
+            last_seen_block: Some(1),
+            total_received: 0,
+            transaction_count: 3,
+            ..summary_0
+        })
+    );
+    assert_eq!(
+        storage.get_account(&address("1")).await.unwrap(),
+        Some(AccountSummary {
+            balance: Some(10),
+            last_seen_block: Some(1),
+            total_sent: 0,
+            transaction_count: 1,
+            ..summary_1
+        })
+    );
+    assert!(storage.rollback_to(0).await.is_ok());
+    assert_eq!(storage.get_account(&address("0")).await.unwrap(), None);
+    assert_eq!(storage.get_account(&address("2")).await.unwrap(), None);
+}
+
+pub async fn pagination<S: Storage>(storage: &mut S) {
+    let transaction = |signature: &str, sender: &str, receiver: &str| Transaction {
+        signature: signature.to_string(),
+        instruction_index: 0,
+        invocation: None,
+        kind: SystemInstructionKind::Transfer,
+        sender: address(sender),
+        receiver: address(receiver),
+        amount: 1,
+    };
+    let block_1 = with_pre_balances(
+        get_block(1, Vec::from([transaction("a", "0", "1"), transaction("b", "0", "2")])),
+        &[("0", 100), ("1", 0), ("2", 0)]
+    );
+    let block_2 = with_pre_balances(
+        get_block(2, Vec::from([transaction("c", "1", "0"), transaction("x", "2", "3")])),
+        &[("3", 0)]
+    );
+    let block_3 = with_pre_balances(get_block(3, Vec::from([transaction("d", "0", "4")])), &[("4", 0)]);
+    for block in [block_1, block_2, block_3] {
+        assert!(storage.add_block(block).await.is_ok());
+    }
+
+    let page = |limit, cursor: Option<&str>, order| Page {limit, cursor: cursor.map(address), order};
+    let addresses = |accounts: Vec<Account>| -> Vec<Address> {
+        accounts.into_iter().map(|account| account.address).collect()
+    };
+    let expected = |names: &[&str]| -> Vec<Address> {
+        names.iter().map(|name| address(name)).collect()
+    };
+    let accounts = storage.get_accounts(&page(2, None, Order::Asc)).await.unwrap();
+    assert_eq!(addresses(accounts.items), expected(&["0", "1"]));
+    assert_eq!(accounts.next_cursor, Some(address("1")));
+    let accounts = storage.get_accounts(&page(2, Some("1"), Order::Asc)).await.unwrap();
+    assert_eq!(addresses(accounts.items), expected(&["2", "3"]));
+    assert_eq!(accounts.next_cursor, Some(address("3")));
+    let accounts = storage.get_accounts(&page(2, Some("3"), Order::Asc)).await.unwrap();
+    assert_eq!(addresses(accounts.items), expected(&["4"]));
+    assert_eq!(accounts.next_cursor, None);
+    let accounts = storage.get_accounts(&page(2, None, Order::Desc)).await.unwrap();
+    assert_eq!(addresses(accounts.items), expected(&["4", "3"]));
+    assert_eq!(accounts.next_cursor, Some(address("3")));
+    let accounts = storage.get_accounts(&page(3, Some("3"), Order::Desc)).await.unwrap();
+    assert_eq!(addresses(accounts.items), expected(&["2", "1", "0"]));
+    assert_eq!(accounts.next_cursor, None);
+    // A full last page doesn't point to an empty one.
+    let accounts = storage.get_accounts(&page(5, None, Order::Asc)).await.unwrap();
+    assert_eq!((accounts.items.len(), accounts.next_cursor), (5, None));
+
+    let page = |limit, cursor: Option<(u64, usize)>, order| Page {
+        limit,
+        cursor: cursor.map(|(block_height, index)| TransactionCursor {block_height, index}),
+        order,
+    };
+    let signatures = |transactions: Vec<TransactionWithMeta>| -> Vec<Signature> {
+        transactions.into_iter().map(|transaction| transaction.data.signature).collect()
+    };
+    let account = address("0");
+    let all = TransactionFilter::default();
+    let transactions = storage.get_transactions(&account, &all, &page(3, None, Order::Asc)).await.unwrap();
+    assert_eq!(signatures(transactions.items), ["a", "b", "c"]);
+    assert_eq!(transactions.next_cursor, Some(TransactionCursor {block_height: 2, index: 0}));
+    let transactions = storage.get_transactions(&account, &all, &page(3, Some((2, 0)), Order::Asc)).await.unwrap();
+    assert_eq!(signatures(transactions.items), ["d"]);
+    assert_eq!(transactions.next_cursor, None);
+    let transactions = storage.get_transactions(&account, &all, &page(2, None, Order::Desc)).await.unwrap();
+    assert_eq!(signatures(transactions.items), ["d", "c"]);
+    assert_eq!(transactions.next_cursor, Some(TransactionCursor {block_height: 2, index: 0}));
+    let transactions = storage.get_transactions(&account, &all, &page(2, Some((2, 0)), Order::Desc)).await.unwrap();
+    assert_eq!(signatures(transactions.items), ["b", "a"]);
+    assert_eq!(transactions.next_cursor, None);
+    // Cursors don't need to point to a transaction of the address.
+    let transactions = storage.get_transactions(&account, &all, &page(10, Some((2, 1)), Order::Asc)).await.unwrap();
+    assert_eq!(signatures(transactions.items), ["d"]);
+    let transactions = storage.get_transactions(&account, &all, &page(10, Some((1, 5)), Order::Desc)).await.unwrap();
+    assert_eq!(signatures(transactions.items), ["b", "a"]);
+    let transactions = storage.get_transactions(&address("5"), &all, &page(10, None, Order::Asc)).await.unwrap();
+    assert!(transactions.items.is_empty());
+}
+
+pub async fn account_order<S: Storage>(storage: &mut S) {
+    // The base58 strings of real keys differ in length, so their order isn't the one of the key bytes.
+    let keys: Vec<Address> = [[0; 32], [1; 32], [9; 32], [200; 32]].into_iter()
+        .map(|bytes| Pubkey::new_from_array(bytes).to_string())
+        .collect();
+    let mut sorted_strings = keys.clone();
+    sorted_strings.sort();
+    assert_ne!(sorted_strings, keys);
+    let transaction = |signature: &str, receiver: &Address| Transaction {
+        signature: signature.to_string(),
+        instruction_index: 0,
+        invocation: None,
+        kind: SystemInstructionKind::Transfer,
+        sender: keys[0].clone(),
+        receiver: receiver.clone(),
+        amount: 0,
+    };
+    let block = get_block(1, Vec::from([
+        transaction("a", &keys[3]),
+        transaction("b", &keys[1]),
+        transaction("c", &keys[2]),
+    ]));
+    assert!(storage.add_block(block).await.is_ok());
+
+    let addresses = |accounts: Vec<Account>| -> Vec<Address> {
+        accounts.into_iter().map(|account| account.address).collect()
+    };
+    let accounts = storage.get_accounts(&Page::default()).await.unwrap();
+    assert_eq!(addresses(accounts.items), keys);
+    let page = |cursor: &Address, order| Page {limit: 10, cursor: Some(cursor.clone()), order};
+    let accounts = storage.get_accounts(&page(&keys[1], Order::Asc)).await.unwrap();
+    assert_eq!(addresses(accounts.items), keys[2..]);
+    let accounts = storage.get_accounts(&page(&keys[3], Order::Desc)).await.unwrap();
+    assert_eq!(addresses(accounts.items), [keys[2].clone(), keys[1].clone(), keys[0].clone()]);
+    // Cursors which are no valid address have no position in the order.
+    let accounts = storage.get_accounts(&page(&"invalid".to_string(), Order::Asc)).await.unwrap();
+    assert!(accounts.items.is_empty());
+}
+
+pub async fn transaction_filter<S: Storage>(storage: &mut S) {
+    let transaction = |signature: &str, sender: &str, receiver: &str, amount| Transaction {
+        signature: signature.to_string(),
+        instruction_index: 0,
+        invocation: None,
+        kind: SystemInstructionKind::Transfer,
+        sender: address(sender),
+        receiver: address(receiver),
+        amount,
+    };
+    let meta = |signature: &str, status: TransactionStatus| TransactionMeta {
+        signature: signature.to_string(),
+        fee: 5000,
+        fee_payer: address("0"),
+        status,
+        compute_units_consumed: None,
+        memo: None,
+    };
+    let mut block_1 = with_pre_balances(
+        get_block(1, Vec::from([transaction("a", "0", "1", 1), transaction("b", "0", "0", 5)])),
+        &[("0", 100), ("1", 0)]
+    );
+    block_1.transaction_metas = Vec::from([
+        meta("a", TransactionStatus::Success),
+        meta("b", TransactionStatus::Failed("insufficient funds".to_string())),
+    ]);
+    let mut block_2 = get_block(2, Vec::from([transaction("c", "1", "0", 10)]));
+    block_2.transactions[0].kind = SystemInstructionKind::CreateAccount;
+    let mut block_3 = with_pre_balances(get_block(3, Vec::from([transaction("d", "0", "2", 20)])), &[("2", 0)]);
+    block_3.transaction_metas = Vec::from([meta("d", TransactionStatus::Success)]);
+    for block in [block_1, block_2, block_3] {
+        assert!(storage.add_block(block).await.is_ok());
+    }
+
+    let account = address("0");
+    let signatures = |transactions: Vec<TransactionWithMeta>| -> Vec<Signature> {
+        transactions.into_iter().map(|transaction| transaction.data.signature).collect()
+    };
+    let cases = [
+        (TransactionFilter::default(), Vec::from(["a", "b", "c", "d"])),
+        (TransactionFilter {from: Some(2), ..Default::default()}, Vec::from(["c", "d"])),
+        (TransactionFilter {to: Some(2), ..Default::default()}, Vec::from(["a", "b", "c"])),
+        (TransactionFilter {from: Some(2), to: Some(2), ..Default::default()}, Vec::from(["c"])),
+        (TransactionFilter {from: Some(4), ..Default::default()}, Vec::new()),
+        (TransactionFilter {to: Some(0), ..Default::default()}, Vec::new()),
+        (TransactionFilter {min_amount: Some(5), ..Default::default()}, Vec::from(["b", "c", "d"])),
+        (TransactionFilter {max_amount: Some(10), ..Default::default()}, Vec::from(["a", "b", "c"])),
+        (TransactionFilter {min_amount: Some(5), max_amount: Some(10), ..Default::default()}, Vec::from(["b", "c"])),
+        (TransactionFilter {direction: Some(Direction::In), ..Default::default()}, Vec::from(["c"])),
+        (TransactionFilter {direction: Some(Direction::Out), ..Default::default()}, Vec::from(["a", "d"])),
+        (TransactionFilter {direction: Some(Direction::SelfTransfer), ..Default::default()}, Vec::from(["b"])),
+        (TransactionFilter {status: Some(StatusFilter::Success), ..Default::default()}, Vec::from(["a", "d"])),
+        (TransactionFilter {status: Some(StatusFilter::Failed), ..Default::default()}, Vec::from(["b"])),
+        (TransactionFilter {kind: Some(SystemInstructionKind::CreateAccount), ..Default::default()}, Vec::from(["c"])),
+        (
+            TransactionFilter {kind: Some(SystemInstructionKind::Transfer), ..Default::default()},
+            Vec::from(["a", "b", "d"]),
+        ),
+        (TransactionFilter {kind: Some(SystemInstructionKind::Assign), ..Default::default()}, Vec::new()),
+        (
+            TransactionFilter {
+                from: Some(2),
+                min_amount: Some(20),
+                direction: Some(Direction::Out),
+                ..Default::default()
+            },
+            Vec::from(["d"]),
+        ),
+    ];
+    for (filter, expected) in cases {
+        let transactions = storage.get_transactions(&account, &filter, &Page::default()).await.unwrap();
+        assert_eq!(signatures(transactions.items), expected, "{:?}", filter);
+    }
+
+    // Pages are filled with matching transactions and their cursors continue after the last one.
+    let outgoing = TransactionFilter {direction: Some(Direction::Out), ..Default::default()};
+    let page = |cursor, order| Page {limit: 1, cursor, order};
+    let transactions = storage.get_transactions(&account, &outgoing, &page(None, Order::Asc)).await.unwrap();
+    assert_eq!(signatures(transactions.items), ["a"]);
+    assert_eq!(transactions.next_cursor, Some(TransactionCursor {block_height: 1, index: 0}));
+    let transactions = storage.get_transactions(&account, &outgoing, &page(transactions.next_cursor, Order::Asc))
+        .await.unwrap();
+    assert_eq!(signatures(transactions.items), ["d"]);
+    assert_eq!(transactions.next_cursor, None);
+    let transactions = storage.get_transactions(&account, &outgoing, &page(None, Order::Desc)).await.unwrap();
+    assert_eq!(signatures(transactions.items), ["d"]);
+    assert_eq!(transactions.next_cursor, Some(TransactionCursor {block_height: 3, index: 0}));
+    let transactions = storage.get_transactions(&account, &outgoing, &page(transactions.next_cursor, Order::Desc))
+        .await.unwrap();
+    assert_eq!(signatures(transactions.items), ["a"]);
+    assert_eq!(transactions.next_cursor, None);
+    // The status and kind are checked before the page is selected as well.
+    let succeeded = TransactionFilter {status: Some(StatusFilter::Success), ..Default::default()};
+    let transactions = storage.get_transactions(&account, &succeeded, &page(None, Order::Asc)).await.unwrap();
+    assert_eq!(signatures(transactions.items), ["a"]);
+    let transactions = storage.get_transactions(&account, &succeeded, &page(transactions.next_cursor, Order::Asc))
+        .await.unwrap();
+    assert_eq!(signatures(transactions.items), ["d"]);
+    assert_eq!(transactions.next_cursor, None);
+    let created = TransactionFilter {kind: Some(SystemInstructionKind::CreateAccount), ..Default::default()};
+    let transactions = storage.get_transactions(&account, &created, &page(None, Order::Desc)).await.unwrap();
+    assert_eq!(signatures(transactions.items), ["c"]);
+    assert_eq!(transactions.next_cursor, None);
+
+    // Block timestamps can decrease with the height, the time range doesn't rely on their order.
+    let mut block_4 = get_block(4, Vec::from([transaction("e", "2", "0", 1)]));
+    block_4.timestamp = 1;
+    assert!(storage.add_block(block_4).await.is_ok());
+    let cases = [
+        (TransactionFilter {from: Some(1), to: Some(1), ..Default::default()}, Vec::from(["a", "b", "e"])),
+        (TransactionFilter {from: Some(2), ..Default::default()}, Vec::from(["c", "d"])),
+        (TransactionFilter {to: Some(2), ..Default::default()}, Vec::from(["a", "b", "c", "e"])),
+    ];
+    for (filter, expected) in cases {
+        let transactions = storage.get_transactions(&account, &filter, &Page::default()).await.unwrap();
+        assert_eq!(signatures(transactions.items), expected, "{:?}", filter);
+    }
+}
+
+pub async fn rollback<S: Storage>(storage: &mut S) {
+    let transaction = |signature: &str, sender: &str, receiver: &str, amount: u64| Transaction {
+        signature: signature.to_string(),
+        instruction_index: 0,
+        invocation: None,
+        kind: SystemInstructionKind::Transfer,
+        sender: address(sender),
+        receiver: address(receiver),
+        amount,
+    };
+    let block_1 = with_pre_balances(
+        get_block(1, Vec::from([transaction("a", "0", "1", 5)])),
+        &[("0", 100), ("1", 50)]
+    );
+    let block_2 = with_pre_balances(
+        get_block(2, Vec::from([transaction("b", "1", "2", 3), transaction("c", "0", "1", 1)])),
+        &[("0", 95), ("1", 55), ("2", 10)]
+    );
+    let block_3 = get_block(3, Vec::from([transaction("d", "2", "3", 1)]));
+    for block in [block_1.clone(), block_2.clone(), block_3] {
+        assert!(storage.add_block(block).await.is_ok());
+    }
+    assert_eq!(storage.get_accounts(&Page::default()).await.unwrap().items.len(), 4);
+
+    // Rolling back to a slot above the last one doesn't change anything.
+    assert!(storage.rollback_to(block_2.slot + 10).await.is_ok());
+    assert_eq!(storage.get_accounts(&Page::default()).await.unwrap().items.len(), 4);
+
+    assert!(storage.rollback_to(block_1.slot).await.is_ok());
+    assert_accounts(storage, &[
+        Account {address: address("0"), balance: 95},
+        Account {address: address("1"), balance: 55},
+    ]).await;
+    assert_eq!(address_transactions(storage, &address("0")).await.len(), 1);
+    assert_eq!(address_transactions(storage, &address("1")).await.len(), 1);
+    assert!(address_transactions(storage, &address("2")).await.is_empty());
+    assert!(storage.get_transactions_by_signature(&"b".to_string()).await.unwrap().is_empty());
+    assert!(storage.get_transactions_by_signature(&"d".to_string()).await.unwrap().is_empty());
+    let day = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+    assert_eq!(storage.get_transactions_by_day(&day).await.unwrap().len(), 1);
+    assert_eq!(storage.get_last_slot().await.unwrap(), Some(block_1.slot));
+    assert_eq!(storage.get_last_block().await.unwrap(), Some(block_1));
+
+    // The rolled back heights can be added again.
+    assert!(storage.add_block(block_2).await.is_ok());
+    assert_accounts(storage, &[
+        Account {address: address("0"), balance: 94},
+        Account {address: address("1"), balance: 53},
+        Account {address: address("2"), balance: 13},
+    ]).await;
+
+    assert!(storage.rollback_to(0).await.is_ok());
+    assert!(storage.get_accounts(&Page::default()).await.unwrap().items.is_empty());
+    assert_eq!(storage.get_last_block().await.unwrap(), None);
+}
+
+pub async fn initial_balances<S: Storage>(storage: &mut S) {
+    let transaction = |sender: &str, receiver: &str, amount: u64| Transaction {
+        signature: "a".to_string(),
+        instruction_index: 0,
+        invocation: None,
+        kind: SystemInstructionKind::Transfer,
+        sender: address(sender),
+        receiver: address(receiver),
+        amount,
+    };
+    // New accounts start with their balance before the block.
+    let block_1 = with_pre_balances(
+        get_block(1, Vec::from([transaction("a", "b", 30)])),
+        &[("a", 100), ("b", 0)]
+    );
+    // Known accounts ignore their balance before the block.
+    let block_2 = with_pre_balances(
+        get_block(2, Vec::from([transaction("c", "a", 2)])),
+        &[("a", 500), ("c", 7)]
+    );
+    // Accounts without known balance start at zero and never go below it.
+    let block_3 = get_block(3, Vec::from([transaction("d", "a", 5)]));
+    for block in [block_1, block_2, block_3] {
+        assert!(storage.add_block(block).await.is_ok());
+    }
+    assert_accounts(storage, &[
+        Account {address: address("a"), balance: 77},
+        Account {address: address("b"), balance: 30},
+        Account {address: address("c"), balance: 5},
+        Account {address: address("d"), balance: 0},
+    ]).await;
+}
+
+pub async fn balance_accounting<S: Storage>(storage: &mut S) {
+    let balance_change = |account: &str, pre_balance: u64, post_balance: u64| BalanceChange {
+        address: address(account),
+        pre_balance,
+        post_balance,
+    };
+    let transaction = Transaction {
+        signature: "a".to_string(),
+        instruction_index: 0,
+        invocation: None,
+        kind: SystemInstructionKind::Transfer,
+        sender: address("a"),
+        receiver: address("b"),
+        amount: 30,
+    };
+    // The balances include fees and rewards which are not visible in the transfers.
+    let mut block_1 = get_block(1, Vec::from([transaction]));
+    block_1.balance_changes = Vec::from([balance_change("a", 100, 65), balance_change("b", 0, 30)]);
+    let mut block_2 = get_block(2, Vec::new());
+    block_2.balance_changes = Vec::from([balance_change("b", 30, 28), balance_change("c", 0, 7)]);
+    for block in [block_1.clone(), block_2] {
+        assert!(storage.add_block(block).await.is_ok());
+    }
+    assert_accounts(storage, &[
+        Account {address: address("a"), balance: 65},
+        Account {address: address("b"), balance: 28},
+        Account {address: address("c"), balance: 7},
+    ]).await;
+
+    // Rolling back restores the balances before the removed blocks.
+    assert!(storage.rollback_to(block_1.slot).await.is_ok());
+    assert_accounts(storage, &[
+        Account {address: address("a"), balance: 65},
+        Account {address: address("b"), balance: 30},
+    ]).await;
+
+    assert!(storage.rollback_to(0).await.is_ok());
+    assert_accounts(storage, &[
+        Account {address: address("a"), balance: 100},
+    ]).await;
+}
+
+pub async fn transaction_meta<S: Storage>(storage: &mut S) {
+    let transaction = |signature: &str, amount: u64| Transaction {
+        signature: signature.to_string(),
+        instruction_index: 0,
+        invocation: None,
+        kind: SystemInstructionKind::Transfer,
+        sender: address("0"),
+        receiver: address("1"),
+        amount,
+    };
+    let meta = |signature: &str, status: TransactionStatus| TransactionMeta {
+        signature: signature.to_string(),
+        fee: 5000,
+        fee_payer: address("0"),
+        status,
+        compute_units_consumed: Some(150),
+        memo: None,
+    };
+    let failed = TransactionStatus::Failed("insufficient funds".to_string());
+    let mut block_1 = with_pre_balances(
+        get_block(1, Vec::from([transaction("a", 10), transaction("b", 20)])),
+        &[("0", 100), ("1", 0)]
+    );
+    block_1.transaction_metas = Vec::from([meta("a", TransactionStatus::Success), meta("b", failed.clone())]);
+    let mut block_2 = get_block(2, Vec::from([transaction("c", 30)]));
+    block_2.transaction_metas = Vec::from([meta("c", failed.clone())]);
+    for block in [block_1.clone(), block_2.clone()] {
+        assert!(storage.add_block(block).await.is_ok());
+    }
+    assert_eq!(storage.get_last_block().await.unwrap(), Some(block_2.clone()));
+
+    // Failed transactions are stored with their status but don't change any balance.
+    assert_accounts(storage, &[
+        Account {address: address("0"), balance: 90},
+        Account {address: address("1"), balance: 10},
+    ]).await;
+    let metas = |transactions: Vec<TransactionWithMeta>| -> Vec<Option<TransactionMeta>> {
+        transactions.into_iter().map(|transaction| transaction.meta).collect()
+    };
+    assert_eq!(
+        metas(address_transactions(storage, &address("0")).await),
+        [
+            Some(meta("a", TransactionStatus::Success)),
+            Some(meta("b", failed.clone())),
+            Some(meta("c", failed.clone())),
+        ]
+    );
+    assert_eq!(
+        metas(storage.get_transactions_by_signature(&"b".to_string()).await.unwrap()),
+        [Some(meta("b", failed))]
+    );
+
+    // Rolling back a failed transaction doesn't change any balance either.
+    assert!(storage.rollback_to(block_1.slot).await.is_ok());
+    assert_accounts(storage, &[
+        Account {address: address("0"), balance: 90},
+        Account {address: address("1"), balance: 10},
+    ]).await;
+    assert!(storage.get_transactions_by_signature(&"c".to_string()).await.unwrap().is_empty());
+    assert!(storage.rollback_to(0).await.is_ok());
+    assert!(storage.get_accounts(&Page::default()).await.unwrap().items.is_empty());
+}
+
+pub async fn token_balances<S: Storage>(storage: &mut S) {
+    let transfer = |signature: &str, mint: &str, source: Option<&str>, destination: Option<&str>, amount: u64| {
+        TokenTransfer {
+            signature: signature.to_string(),
+            instruction_index: 0,
+            invocation: None,
+            kind: match (source, destination) {
+                (None, _) => TokenTransferKind::MintTo,
+                (_, None) => TokenTransferKind::Burn,
+                _ => TokenTransferKind::Transfer,
+            },
+            mint: address(mint),
+            decimals: 6,
+            source: source.map(address),
+            destination: destination.map(address),
+            amount,
+        }
+    };
+    let change = |account: &str, owner: &str, mint: &str, pre_amount: Option<u64>, post_amount: Option<u64>| {
+        TokenBalanceChange {
+            account: address(account),
+            owner: address(owner),
+            mint: address(mint),
+            decimals: 6,
+            pre_amount,
+            post_amount,
+        }
+    };
+    let balance = |mint: &str, amount: u64| TokenBalance {mint: address(mint), decimals: 6, amount};
+    // Token amounts can exceed the range of signed integers.
+    let large = u64::MAX - 10;
+
+    // Owner "a" holds two token accounts of mint "x" and one of mint "y".
+    let mut block_1 = get_block(1, Vec::new());
+    block_1.token_transfers = Vec::from([
+        transfer("a", "x", None, Some("a1"), 100),
+        transfer("b", "y", None, Some("a3"), large),
+    ]);
+    block_1.token_balance_changes = Vec::from([
+        change("a1", "a", "x", None, Some(100)),
+        change("a2", "a", "x", Some(50), Some(50)),
+        change("a3", "a", "y", None, Some(large)),
+    ]);
+    let mut block_2 = get_block(2, Vec::new());
+    block_2.token_transfers = Vec::from([
+        transfer("c", "x", Some("a1"), Some("b1"), 30),
+        transfer("d", "x", Some("a2"), None, 50),
+    ]);
+    block_2.token_balance_changes = Vec::from([
+        change("a1", "a", "x", Some(100), Some(70)),
+        change("b1", "b", "x", None, Some(30)),
+        change("a2", "a", "x", Some(50), None),
+    ]);
+    for block in [block_1.clone(), block_2.clone()] {
+        assert!(storage.add_block(block).await.is_ok());
+    }
+    assert_eq!(storage.get_last_block().await.unwrap(), Some(block_2.clone()));
+    assert_eq!(
+        storage.get_token_balances(&address("a")).await.unwrap(),
+        [balance("x", 70), balance("y", large)]
+    );
+    assert_eq!(storage.get_token_balances(&address("b")).await.unwrap(), [balance("x", 30)]);
+    assert!(storage.get_token_balances(&address("c")).await.unwrap().is_empty());
+
+    let transfers = |transfers: Vec<TransactionWithMeta<TokenTransfer>>| -> Vec<TokenTransfer> {
+        transfers.into_iter().map(|transfer| transfer.data).collect()
+    };
+    assert_eq!(
+        transfers(storage.get_token_transfers_by_mint(&address("x")).await.unwrap()),
+        [
+            block_1.token_transfers[0].clone(),
+            block_2.token_transfers[0].clone(),
+            block_2.token_transfers[1].clone(),
+        ]
+    );
+    assert_eq!(
+        transfers(storage.get_token_transfers_by_mint(&address("y")).await.unwrap()),
+        [block_1.token_transfers[1].clone()]
+    );
+
+    // Rolling back restores the token accounts from before the removed blocks.
+    assert!(storage.rollback_to(block_1.slot).await.is_ok());
+    assert_eq!(
+        storage.get_token_balances(&address("a")).await.unwrap(),
+        [balance("x", 150), balance("y", large)]
+    );
+    assert!(storage.get_token_balances(&address("b")).await.unwrap().is_empty());
+    assert_eq!(storage.get_token_transfers_by_mint(&address("x")).await.unwrap().len(), 1);
+
+    assert!(storage.rollback_to(0).await.is_ok());
+    assert_eq!(storage.get_token_balances(&address("a")).await.unwrap(), [balance("x", 50)]);
+    assert!(storage.get_token_transfers_by_mint(&address("y")).await.unwrap().is_empty());
+}
+
+pub async fn raw_instructions<S: Storage>(storage: &mut S) {
+    let instruction = |signature: &str, program_id: &str, invocation: Option<Invocation>| {
+        RawInstruction {
+            signature: signature.to_string(),
+            instruction_index: 1,
+            invocation,
+            program_id: address(program_id),
+            accounts: Vec::from([address("a"), address("b")]),
+            data: "3Bxs".to_string(),
+        }
+    };
+    let mut block_1 = get_block(1, Vec::new());
+    block_1.raw_instructions = Vec::from([
+        instruction("a", "p", None),
+        instruction("a", "q", Some(Invocation {program_id: address("p"), inner_index: 0})),
+    ]);
+    block_1.transaction_metas = Vec::from([
+        TransactionMeta {
+            signature: "a".to_string(),
+            fee: 5000,
+            fee_payer: address("a"),
+            status: TransactionStatus::Success,
+            compute_units_consumed: None,
+            memo: None,
+        },
+    ]);
+    let mut block_2 = get_block(2, Vec::new());
+    block_2.raw_instructions = Vec::from([instruction("b", "p", None)]);
+    for block in [block_1.clone(), block_2.clone()] {
+        assert!(storage.add_block(block).await.is_ok());
+    }
+    assert_eq!(storage.get_last_block().await.unwrap(), Some(block_2.clone()));
+    assert_eq!(
+        storage.get_instructions_by_program(&address("p")).await.unwrap(),
+        [
+            TransactionWithMeta {
+                data: block_1.raw_instructions[0].clone(),
+                timestamp: block_1.timestamp,
+                meta: Some(block_1.transaction_metas[0].clone()),
+            },
+            TransactionWithMeta {data: block_2.raw_instructions[0].clone(), timestamp: block_2.timestamp, meta: None},
+        ]
+    );
+    assert_eq!(storage.get_instructions_by_program(&address("q")).await.unwrap().len(), 1);
+    assert!(storage.get_instructions_by_program(&address("a")).await.unwrap().is_empty());
+
+    assert!(storage.rollback_to(block_1.slot).await.is_ok());
+    assert_eq!(storage.get_instructions_by_program(&address("p")).await.unwrap().len(), 1);
+    assert!(storage.rollback_to(0).await.is_ok());
+    assert!(storage.get_instructions_by_program(&address("p")).await.unwrap().is_empty());
+    assert!(storage.get_instructions_by_program(&address("q")).await.unwrap().is_empty());
+}
+
+pub async fn custom_instructions<S: Storage>(storage: &mut S) {
+    let instruction = |signature: &str, invocation: Option<Invocation>| CustomInstruction {
+        signature: signature.to_string(),
+        instruction_index: 2,
+        invocation,
+        program_id: address("p"),
+        name: "swap".to_string(),
+        data: serde_json::json!({"amount_in": 10, "route": [address("a"), address("b")]}),
+    };
+    let mut block_1 = get_block(1, Vec::new());
+    block_1.custom_instructions = Vec::from([
+        instruction("a", Some(Invocation {program_id: address("q"), inner_index: 1})),
+    ]);
+    block_1.transaction_metas = Vec::from([
+        TransactionMeta {
+            signature: "a".to_string(),
+            fee: 5000,
+            fee_payer: address("a"),
+            status: TransactionStatus::Success,
+            compute_units_consumed: Some(100),
+            memo: None,
+        },
+    ]);
+    let mut block_2 = get_block(2, Vec::new());
+    block_2.custom_instructions = Vec::from([instruction("b", None)]);
+    for block in [block_1.clone(), block_2.clone()] {
+        assert!(storage.add_block(block).await.is_ok());
+    }
+    assert_eq!(storage.get_last_block().await.unwrap(), Some(block_2.clone()));
+    assert_eq!(
+        storage.get_custom_instructions_by_program(&address("p")).await.unwrap(),
+        [
+            TransactionWithMeta {
+                data: block_1.custom_instructions[0].clone(),
+                timestamp: block_1.timestamp,
+                meta: Some(block_1.transaction_metas[0].clone()),
+            },
+            TransactionWithMeta {
+                data: block_2.custom_instructions[0].clone(),
+                timestamp: block_2.timestamp,
+                meta: None,
+            },
+        ]
+    );
+    assert!(storage.get_custom_instructions_by_program(&address("q")).await.unwrap().is_empty());
+
+    assert!(storage.rollback_to(block_1.slot).await.is_ok());
+    assert_eq!(storage.get_custom_instructions_by_program(&address("p")).await.unwrap().len(), 1);
+    assert!(storage.rollback_to(0).await.is_ok());
+    assert!(storage.get_custom_instructions_by_program(&address("p")).await.unwrap().is_empty());
+}
+
+pub async fn transactions_by_memo<S: Storage>(storage: &mut S) {
+    let transaction = |signature: &str| Transaction {
+        signature: signature.to_string(),
+        instruction_index: 0,
+        invocation: None,
+        kind: SystemInstructionKind::Transfer,
+        sender: address("0"),
+        receiver: address("1"),
+        amount: 1,
+    };
+    let meta = |signature: &str, memo: Option<&str>| TransactionMeta {
+        signature: signature.to_string(),
+        fee: 5000,
+        fee_payer: address("0"),
+        status: TransactionStatus::Success,
+        compute_units_consumed: None,
+        memo: memo.map(str::to_string),
+    };
+    let mut block_1 = with_pre_balances(
+        get_block(1, Vec::from([transaction("a"), transaction("b"), transaction("b")])),
+        &[("0", 100), ("1", 0)]
+    );
+    block_1.transaction_metas = Vec::from([meta("a", Some("order-1")), meta("b", Some("order-12"))]);
+    let mut block_2 = get_block(2, Vec::from([transaction("c"), transaction("d")]));
+    block_2.transaction_metas = Vec::from([meta("c", Some("other")), meta("d", None)]);
+    for block in [block_1.clone(), block_2.clone()] {
+        assert!(storage.add_block(block).await.is_ok());
+    }
+    assert_eq!(storage.get_last_block().await.unwrap(), Some(block_2.clone()));
+
+    let signatures = |transactions: Vec<TransactionWithMeta>| -> Vec<Signature> {
+        transactions.into_iter().map(|transaction| transaction.data.signature).collect()
+    };
+    let exact = |memo: &str| MemoFilter::Exact(memo.to_string());
+    let prefix = |memo: &str| MemoFilter::Prefix(memo.to_string());
+    // All transfers of a transaction carry its memo.
+    assert_eq!(signatures(storage.get_transactions_by_memo(&exact("order-1")).await.unwrap()), ["a"]);
+    assert_eq!(signatures(storage.get_transactions_by_memo(&exact("order-12")).await.unwrap()), ["b", "b"]);
+    assert_eq!(signatures(storage.get_transactions_by_memo(&prefix("order-1")).await.unwrap()), ["a", "b", "b"]);
+    assert_eq!(signatures(storage.get_transactions_by_memo(&prefix("order-12")).await.unwrap()), ["b", "b"]);
+    assert!(storage.get_transactions_by_memo(&prefix("order-2")).await.unwrap().is_empty());
+    assert_eq!(signatures(storage.get_transactions_by_memo(&prefix("")).await.unwrap()), ["a", "b", "b", "c"]);
+    assert!(storage.get_transactions_by_memo(&exact("order")).await.unwrap().is_empty());
+    assert_eq!(
+        storage.get_transactions_by_memo(&exact("other")).await.unwrap()[0].meta,
+        Some(meta("c", Some("other")))
+    );
+
+    assert!(storage.rollback_to(block_1.slot).await.is_ok());
+    assert_eq!(signatures(storage.get_transactions_by_memo(&prefix("o")).await.unwrap()), ["a", "b", "b"]);
+    assert!(storage.rollback_to(0).await.is_ok());
+    assert!(storage.get_transactions_by_memo(&prefix("")).await.unwrap().is_empty());
+}
+
+pub async fn stake_and_votes<S: Storage>(storage: &mut S) {
+    let instruction = |signature: &str, kind, stake_account: &str, vote_account: Option<&str>| StakeInstruction {
+        signature: signature.to_string(),
+        instruction_index: 0,
+        invocation: None,
+        kind,
+        stake_account: address(stake_account),
+        vote_account: vote_account.map(address),
+        destination: None,
+        amount: 0,
+    };
+    let vote = |signature: &str, slot: Option<u64>| Vote {
+        signature: signature.to_string(),
+        instruction_index: 0,
+        invocation: None,
+        vote_account: address("v"),
+        authority: address("x"),
+        slot,
+    };
+    let meta = |signature: &str, status: TransactionStatus| TransactionMeta {
+        signature: signature.to_string(),
+        fee: 5000,
+        fee_payer: address("x"),
+        status,
+        compute_units_consumed: None,
+        memo: None,
+    };
+    let mut block_1 = get_block(1, Vec::new());
+    block_1.stake_instructions = Vec::from([
+        instruction("a", StakeInstructionKind::Initialize, "s", None),
+        instruction("a", StakeInstructionKind::Delegate, "s", Some("v")),
+    ]);
+    block_1.votes = Vec::from([vote("b", Some(1))]);
+    let withdraw = StakeInstruction {
+        destination: Some(address("w")),
+        amount: 400,
+        ..instruction("d", StakeInstructionKind::Withdraw, "s", None)
+    };
+    let mut block_2 = with_pre_balances(get_block(2, Vec::new()), &[("s", 1000), ("w", 0)]);
+    block_2.stake_instructions = Vec::from([
+        instruction("c", StakeInstructionKind::Deactivate, "s", None),
+        withdraw.clone(),
+        instruction("e", StakeInstructionKind::Delegate, "t", Some("v")),
+    ]);
+    block_2.votes = Vec::from([vote("f", Some(3)), vote("g", Some(5)), vote("h", None)]);
+    block_2.transaction_metas = Vec::from([
+        meta("e", TransactionStatus::Failed("InstructionError".to_string())),
+        meta("g", TransactionStatus::Failed("InstructionError".to_string())),
+    ]);
+
+    assert!(storage.add_block(block_1.clone()).await.is_ok());
+    let stake_account = storage.get_stake_account(&address("s")).await.unwrap().unwrap();
+    assert_eq!(stake_account.vote_account, Some(address("v")));
+    assert!(!stake_account.deactivated);
+    assert_eq!(stake_account.balance, None);
+    assert_eq!(stake_account.instructions.len(), 2);
+    let validator = Validator {
+        vote_account: address("v"),
+        votes: 1,
+        last_voted_slot: Some(1),
+        last_vote_timestamp: Some(block_1.timestamp),
+        stake_accounts: Vec::from([address("s")]),
+    };
+    assert_eq!(storage.get_validator(&address("v")).await.unwrap(), Some(validator.clone()));
+
+    assert!(storage.add_block(block_2.clone()).await.is_ok());
+    assert_eq!(storage.get_last_block().await.unwrap(), Some(block_2.clone()));
+    let stake_account = storage.get_stake_account(&address("s")).await.unwrap().unwrap();
+    assert_eq!(stake_account.vote_account, Some(address("v")));
+    assert!(stake_account.deactivated);
+    assert_eq!(stake_account.balance, Some(600));
+    assert_eq!(stake_account.instructions.len(), 4);
+    // Withdrawals show up for their destination, but don't make it a stake account.
+    let destination = storage.get_stake_account(&address("w")).await.unwrap().unwrap();
+    assert_eq!(destination.vote_account, None);
+    assert_eq!(destination.balance, Some(400));
+    assert_eq!(
+        destination.instructions,
+        [TransactionWithMeta {data: withdraw, timestamp: block_2.timestamp, meta: None}]
+    );
+    // Failed delegations are kept but don't delegate.
+    let failed = storage.get_stake_account(&address("t")).await.unwrap().unwrap();
+    assert_eq!(failed.vote_account, None);
+    assert_eq!(failed.instructions.len(), 1);
+    assert_eq!(
+        storage.get_validator(&address("v")).await.unwrap(),
+        Some(Validator {
+            votes: 3,
+            last_voted_slot: Some(3),
+            last_vote_timestamp: Some(block_2.timestamp),
+            stake_accounts: Vec::new(),
+            ..validator.clone()
+        })
+    );
+    assert!(storage.get_stake_account(&address("v")).await.unwrap().is_none());
+    assert!(storage.get_validator(&address("s")).await.unwrap().is_none());
+    assert_accounts(
+        storage,
+        &[Account {address: address("s"), balance: 600}, Account {address: address("w"), balance: 400}]
+    ).await;
+
+    assert!(storage.rollback_to(block_1.slot).await.is_ok());
+    assert_eq!(storage.get_validator(&address("v")).await.unwrap(), Some(validator));
+    assert!(storage.get_stake_account(&address("w")).await.unwrap().is_none());
+    assert!(storage.get_stake_account(&address("t")).await.unwrap().is_none());
+    assert_accounts(storage, &[Account {address: address("s"), balance: 1000}]).await;
+    assert!(storage.rollback_to(0).await.is_ok());
+    assert!(storage.get_stake_account(&address("s")).await.unwrap().is_none());
+    assert!(storage.get_validator(&address("v")).await.unwrap().is_none());
+    assert_accounts(storage, &[]).await;
+}
+
+pub async fn rewards<S: Storage>(storage: &mut S) {
+    let reward = |account: &str, lamports: i64, post_balance: u64, kind: RewardKind| Reward {
+        address: address(account),
+        lamports,
+        post_balance,
+        kind: Some(kind),
+        commission: (kind == RewardKind::Voting).then_some(10),
+    };
+    let transfer = Transaction {
+        signature: "a".to_string(),
+        instruction_index: 0,
+        invocation: None,
+        kind: SystemInstructionKind::Transfer,
+        sender: address("0"),
+        receiver: address("1"),
+        amount: 10,
+    };
+    let mut block_1 = with_pre_balances(
+        get_block(1, Vec::from([transfer])),
+        &[("0", 100), ("1", 0), ("v", 1000)]
+    );
+    block_1.rewards = Vec::from([
+        reward("v", 50, 1050, RewardKind::Voting),
+        reward("0", -2, 88, RewardKind::Rent),
+    ]);
+    let mut block_2 = with_pre_balances(get_block(2, Vec::new()), &[("v", 1050)]);
+    block_2.rewards = Vec::from([reward("v", 5, 1055, RewardKind::Fee)]);
+    for block in [block_1.clone(), block_2.clone()] {
+        assert!(storage.add_block(block).await.is_ok());
+    }
+    assert_eq!(storage.get_last_block().await.unwrap(), Some(block_2.clone()));
+    assert_accounts(storage, &[
+        Account {address: address("0"), balance: 88},
+        Account {address: address("1"), balance: 10},
+        Account {address: address("v"), balance: 1055},
+    ]).await;
+    assert_eq!(
+        storage.get_rewards(&address("v")).await.unwrap(),
+        [
+            BlockReward {slot: block_1.slot, timestamp: block_1.timestamp, reward: block_1.rewards[0].clone()},
+            BlockReward {slot: block_2.slot, timestamp: block_2.timestamp, reward: block_2.rewards[0].clone()},
+        ]
+    );
+    assert_eq!(storage.get_rewards(&address("0")).await.unwrap().len(), 1);
+    assert!(storage.get_rewards(&address("1")).await.unwrap().is_empty());
+    assert_eq!(storage.get_block_rewards(block_1.height).await.unwrap(), Some(block_1.rewards.clone()));
+    assert_eq!(storage.get_block_rewards(block_2.height).await.unwrap(), Some(block_2.rewards.clone()));
+    assert_eq!(storage.get_block_rewards(block_2.height + 1).await.unwrap(), None);
+
+    assert!(storage.rollback_to(block_1.slot).await.is_ok());
+    assert_accounts(storage, &[
+        Account {address: address("0"), balance: 88},
+        Account {address: address("1"), balance: 10},
+        Account {address: address("v"), balance: 1050},
+    ]).await;
+    assert_eq!(storage.get_rewards(&address("v")).await.unwrap().len(), 1);
+    assert_eq!(storage.get_block_rewards(block_2.height).await.unwrap(), None);
+    assert!(storage.rollback_to(0).await.is_ok());
+    assert_accounts(storage, &[]).await;
+    assert!(storage.get_rewards(&address("v")).await.unwrap().is_empty());
+    assert_eq!(storage.get_block_rewards(block_1.height).await.unwrap(), None);
+}
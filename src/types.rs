@@ -1,32 +1,655 @@
+use chrono::{DateTime, NaiveDate};
 use serde::{Deserialize, Serialize};
+use solana_sdk::clock::Slot;
+use solana_sdk::pubkey::{ParsePubkeyError, Pubkey};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 
 pub type Hash = String;
 pub type Address = String;
+pub type Signature = String;
 
+/// Parses the base58 encoded public key `address`.
+pub fn parse_address(address: &str) -> Result<Pubkey, ParsePubkeyError> {
+    Pubkey::from_str(address)
+}
+
+/// The system program instructions. Only the transfers, account creations and nonce withdrawals
+/// move lamports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemInstructionKind {
+    Transfer,
+    TransferWithSeed,
+    CreateAccount,
+    CreateAccountWithSeed,
+    WithdrawFromNonce,
+    Assign,
+    AssignWithSeed,
+    Allocate,
+    AllocateWithSeed,
+    AdvanceNonce,
+    InitializeNonce,
+    AuthorizeNonce,
+    UpgradeNonce,
+}
+
+/// The origin of an instruction which was executed through a cross-program invocation.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Invocation<A = Address> {
+    /// The program of the outer instruction which invoked it.
+    pub program_id: A,
+    /// The position among the inner instructions of the outer instruction.
+    pub inner_index: usize,
+}
+
+impl<A> Invocation<A> {
+    pub fn map_addresses<B>(&self, f: &impl Fn(&A) -> B) -> Invocation<B> {
+        Invocation {program_id: f(&self.program_id), inner_index: self.inner_index}
+    }
+}
+
+/// A system program instruction. Instructions which don't move lamports have the account they
+/// affect as sender and receiver and an amount of zero.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Transaction<A = Address> {
+    pub signature: Signature,
+    /// The index of the outer instruction for inner instructions.
+    pub instruction_index: usize,
+    pub invocation: Option<Invocation<A>>,
+    pub kind: SystemInstructionKind,
+    pub sender: A,
+    pub receiver: A,
+    pub amount: u64,
+}
+
+impl<A> Transaction<A> {
+    pub fn map_addresses<B>(&self, f: &impl Fn(&A) -> B) -> Transaction<B> {
+        Transaction {
+            signature: self.signature.clone(),
+            instruction_index: self.instruction_index,
+            invocation: self.invocation.as_ref().map(|invocation| invocation.map_addresses(f)),
+            kind: self.kind,
+            sender: f(&self.sender),
+            receiver: f(&self.receiver),
+            amount: self.amount,
+        }
+    }
+}
+
+/// The result of a transaction, with the error message if it failed.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "status", content = "error", rename_all = "snake_case")]
+pub enum TransactionStatus {
+    Success,
+    Failed(String),
+}
+
+impl TransactionStatus {
+    pub fn is_success(&self) -> bool {
+        matches!(self, TransactionStatus::Success)
+    }
+}
+
+/// The metadata of an on-chain transaction, shared by all transactions parsed from it.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct TransactionMeta<A = Address> {
+    pub signature: Signature,
+    pub fee: u64,
+    pub fee_payer: A,
+    #[serde(flatten)]
+    pub status: TransactionStatus,
+    pub compute_units_consumed: Option<u64>,
+    /// The SPL Memos attached to the transaction, joined with `; ` if there are multiple.
+    pub memo: Option<String>,
+}
+
+impl<A> TransactionMeta<A> {
+    pub fn map_addresses<B>(&self, f: &impl Fn(&A) -> B) -> TransactionMeta<B> {
+        TransactionMeta {
+            signature: self.signature.clone(),
+            fee: self.fee,
+            fee_payer: f(&self.fee_payer),
+            status: self.status.clone(),
+            compute_units_consumed: self.compute_units_consumed,
+            memo: self.memo.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenTransferKind {
+    Transfer,
+    MintTo,
+    Burn,
+}
+
+/// A movement of SPL tokens between token accounts. Minting has no source and burning no
+/// destination.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct TokenTransfer<A = Address> {
+    pub signature: Signature,
+    /// The index of the outer instruction for inner instructions.
+    pub instruction_index: usize,
+    pub invocation: Option<Invocation<A>>,
+    pub kind: TokenTransferKind,
+    pub mint: A,
+    pub decimals: u8,
+    pub source: Option<A>,
+    pub destination: Option<A>,
+    pub amount: u64,
+}
+
+impl<A> TokenTransfer<A> {
+    pub fn map_addresses<B>(&self, f: &impl Fn(&A) -> B) -> TokenTransfer<B> {
+        TokenTransfer {
+            signature: self.signature.clone(),
+            instruction_index: self.instruction_index,
+            invocation: self.invocation.as_ref().map(|invocation| invocation.map_addresses(f)),
+            kind: self.kind,
+            mint: f(&self.mint),
+            decimals: self.decimals,
+            source: self.source.as_ref().map(f),
+            destination: self.destination.as_ref().map(f),
+            amount: self.amount,
+        }
+    }
+}
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StakeInstructionKind {
+    Initialize,
+    Delegate,
+    Deactivate,
+    Withdraw,
+    Split,
+    Merge,
+}
+
+/// A stake program instruction. Withdrawals, splits and merges move `amount` lamports from the
+/// stake account to `destination`.
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
-pub struct Transaction {
-    pub sender: Address,
-    pub receiver: Address,
+pub struct StakeInstruction<A = Address> {
+    pub signature: Signature,
+    /// The index of the outer instruction for inner instructions.
+    pub instruction_index: usize,
+    pub invocation: Option<Invocation<A>>,
+    pub kind: StakeInstructionKind,
+    pub stake_account: A,
+    /// The validator the stake gets delegated to.
+    pub vote_account: Option<A>,
+    pub destination: Option<A>,
     pub amount: u64,
 }
 
+impl<A> StakeInstruction<A> {
+    pub fn map_addresses<B>(&self, f: &impl Fn(&A) -> B) -> StakeInstruction<B> {
+        StakeInstruction {
+            signature: self.signature.clone(),
+            instruction_index: self.instruction_index,
+            invocation: self.invocation.as_ref().map(|invocation| invocation.map_addresses(f)),
+            kind: self.kind,
+            stake_account: f(&self.stake_account),
+            vote_account: self.vote_account.as_ref().map(f),
+            destination: self.destination.as_ref().map(f),
+            amount: self.amount,
+        }
+    }
+}
+
+/// A vote program instruction casting a vote of a validator.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Vote<A = Address> {
+    pub signature: Signature,
+    /// The index of the outer instruction for inner instructions.
+    pub instruction_index: usize,
+    pub invocation: Option<Invocation<A>>,
+    pub vote_account: A,
+    pub authority: A,
+    /// The last slot voted on, if the vote contains any.
+    pub slot: Option<Slot>,
+}
+
+impl<A> Vote<A> {
+    pub fn map_addresses<B>(&self, f: &impl Fn(&A) -> B) -> Vote<B> {
+        Vote {
+            signature: self.signature.clone(),
+            instruction_index: self.instruction_index,
+            invocation: self.invocation.as_ref().map(|invocation| invocation.map_addresses(f)),
+            vote_account: f(&self.vote_account),
+            authority: f(&self.authority),
+            slot: self.slot,
+        }
+    }
+}
+
+/// An instruction of a program the RPC interface couldn't parse and no parser is registered for.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct RawInstruction<A = Address> {
+    pub signature: Signature,
+    /// The index of the outer instruction for inner instructions.
+    pub instruction_index: usize,
+    pub invocation: Option<Invocation<A>>,
+    pub program_id: A,
+    pub accounts: Vec<A>,
+    /// The base58 encoded instruction data.
+    pub data: String,
+}
+
+impl<A> RawInstruction<A> {
+    pub fn map_addresses<B>(&self, f: &impl Fn(&A) -> B) -> RawInstruction<B> {
+        RawInstruction {
+            signature: self.signature.clone(),
+            instruction_index: self.instruction_index,
+            invocation: self.invocation.as_ref().map(|invocation| invocation.map_addresses(f)),
+            program_id: f(&self.program_id),
+            accounts: self.accounts.iter().map(f).collect(),
+            data: self.data.clone(),
+        }
+    }
+}
+
+/// An instruction a registered parser decoded into data of its own, see `InstructionEvent::Custom`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct CustomInstruction<A = Address> {
+    pub signature: Signature,
+    /// The index of the outer instruction for inner instructions.
+    pub instruction_index: usize,
+    pub invocation: Option<Invocation<A>>,
+    pub program_id: A,
+    /// What the instruction does, named by the parser.
+    pub name: String,
+    pub data: serde_json::Value,
+}
+
+impl<A> CustomInstruction<A> {
+    pub fn map_addresses<B>(&self, f: &impl Fn(&A) -> B) -> CustomInstruction<B> {
+        CustomInstruction {
+            signature: self.signature.clone(),
+            instruction_index: self.instruction_index,
+            invocation: self.invocation.as_ref().map(|invocation| invocation.map_addresses(f)),
+            program_id: f(&self.program_id),
+            name: self.name.clone(),
+            data: self.data.clone(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
-pub struct TransactionWithMeta {
-    pub data: Transaction,
+pub struct TransactionWithMeta<Data = Transaction> {
+    pub data: Data,
     pub timestamp: i64,
+    /// Not available for blocks stored before the metadata was tracked.
+    pub meta: Option<TransactionMeta>,
+}
+
+/// The lamport balance of an account before the first and after the last transaction of a block
+/// which involved the account, including the block rewards.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct BalanceChange<A = Address> {
+    pub address: A,
+    pub pre_balance: u64,
+    pub post_balance: u64,
+}
+
+impl<A> BalanceChange<A> {
+    pub fn map_addresses<B>(&self, f: &impl Fn(&A) -> B) -> BalanceChange<B> {
+        BalanceChange {address: f(&self.address), pre_balance: self.pre_balance, post_balance: self.post_balance}
+    }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct Block {
+/// The token amount of a token account before the first and after the last transaction of a
+/// block which involved the account. The amount is `None` if the account didn't exist.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct TokenBalanceChange<A = Address> {
+    pub account: A,
+    pub owner: A,
+    pub mint: A,
+    pub decimals: u8,
+    pub pre_amount: Option<u64>,
+    pub post_amount: Option<u64>,
+}
+
+impl<A> TokenBalanceChange<A> {
+    pub fn map_addresses<B>(&self, f: &impl Fn(&A) -> B) -> TokenBalanceChange<B> {
+        TokenBalanceChange {
+            account: f(&self.account),
+            owner: f(&self.owner),
+            mint: f(&self.mint),
+            decimals: self.decimals,
+            pre_amount: self.pre_amount,
+            post_amount: self.post_amount,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RewardKind {
+    Fee,
+    Rent,
+    Staking,
+    Voting,
+}
+
+/// A reward credited to an account with a block, or rent collected from it if `lamports` is
+/// negative.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Reward<A = Address> {
+    pub address: A,
+    pub lamports: i64,
+    pub post_balance: u64,
+    pub kind: Option<RewardKind>,
+    /// The commission of the vote account for staking and voting rewards.
+    pub commission: Option<u8>,
+}
+
+impl<A> Reward<A> {
+    pub fn map_addresses<B>(&self, f: &impl Fn(&A) -> B) -> Reward<B> {
+        Reward {
+            address: f(&self.address),
+            lamports: self.lamports,
+            post_balance: self.post_balance,
+            kind: self.kind,
+            commission: self.commission,
+        }
+    }
+}
+
+/// A reward together with the block it was credited with.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct BlockReward {
+    pub slot: Slot,
+    pub timestamp: i64,
+    pub reward: Reward,
+}
+
+/// A block with the addresses as base58 strings, or in any other form `A` like the public keys the memory
+/// storage keeps.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Block<A = Address> {
+    pub slot: Slot,
+    pub parent_slot: Slot,
     pub height: u64,
     pub hash: Hash,
+    pub previous_blockhash: Hash,
     pub timestamp: i64,
-    pub transactions: Vec<Transaction>,
+    pub transactions: Vec<Transaction<A>>,
+    pub balance_changes: Vec<BalanceChange<A>>,
+    pub transaction_metas: Vec<TransactionMeta<A>>,
+    pub token_transfers: Vec<TokenTransfer<A>>,
+    pub token_balance_changes: Vec<TokenBalanceChange<A>>,
+    pub raw_instructions: Vec<RawInstruction<A>>,
+    pub custom_instructions: Vec<CustomInstruction<A>>,
+    pub stake_instructions: Vec<StakeInstruction<A>>,
+    pub votes: Vec<Vote<A>>,
+    pub rewards: Vec<Reward<A>>,
+}
+
+/// The header of a block together with its parsed system transactions.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct BlockSummary {
+    pub height: u64,
+    pub slot: Slot,
+    pub parent_slot: Slot,
+    pub hash: Hash,
+    pub previous_blockhash: Hash,
+    pub timestamp: i64,
+    pub transfer_count: usize,
+    pub transfers: Vec<TransactionWithMeta>,
+}
+
+impl From<Block> for BlockSummary {
+    fn from(block: Block) -> Self {
+        let transfers: Vec<TransactionWithMeta> = block.transactions.iter()
+            .map(|transaction| TransactionWithMeta {
+                data: transaction.clone(),
+                timestamp: block.timestamp,
+                meta: block.transaction_meta(&transaction.signature).cloned(),
+            })
+            .collect();
+        Self {
+            height: block.height,
+            slot: block.slot,
+            parent_slot: block.parent_slot,
+            hash: block.hash,
+            previous_blockhash: block.previous_blockhash,
+            timestamp: block.timestamp,
+            transfer_count: transfers.len(),
+            transfers,
+        }
+    }
+}
+
+impl<A> Block<A> {
+    pub fn map_addresses<B>(&self, f: &impl Fn(&A) -> B) -> Block<B> {
+        Block {
+            slot: self.slot,
+            parent_slot: self.parent_slot,
+            height: self.height,
+            hash: self.hash.clone(),
+            previous_blockhash: self.previous_blockhash.clone(),
+            timestamp: self.timestamp,
+            transactions: self.transactions.iter().map(|transaction| transaction.map_addresses(f)).collect(),
+            balance_changes: self.balance_changes.iter().map(|change| change.map_addresses(f)).collect(),
+            transaction_metas: self.transaction_metas.iter().map(|meta| meta.map_addresses(f)).collect(),
+            token_transfers: self.token_transfers.iter().map(|transfer| transfer.map_addresses(f)).collect(),
+            token_balance_changes: self.token_balance_changes.iter().map(|change| change.map_addresses(f)).collect(),
+            raw_instructions: self.raw_instructions.iter().map(|instruction| instruction.map_addresses(f)).collect(),
+            custom_instructions: self.custom_instructions.iter()
+                .map(|instruction| instruction.map_addresses(f))
+                .collect(),
+            stake_instructions: self.stake_instructions.iter()
+                .map(|instruction| instruction.map_addresses(f))
+                .collect(),
+            votes: self.votes.iter().map(|vote| vote.map_addresses(f)).collect(),
+            rewards: self.rewards.iter().map(|reward| reward.map_addresses(f)).collect(),
+        }
+    }
+
+    /// Whether the block contains nothing to store besides its place in the chain.
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+            && self.balance_changes.is_empty()
+            && self.transaction_metas.is_empty()
+            && self.token_transfers.is_empty()
+            && self.token_balance_changes.is_empty()
+            && self.raw_instructions.is_empty()
+            && self.custom_instructions.is_empty()
+            && self.stake_instructions.is_empty()
+            && self.votes.is_empty()
+            && self.rewards.is_empty()
+    }
+
+    /// The UTC day the block was produced at.
+    pub fn day(&self) -> Option<NaiveDate> {
+        DateTime::from_timestamp(self.timestamp, 0).map(|time| time.date_naive())
+    }
+
+    pub fn transaction_meta(&self, signature: &Signature) -> Option<&TransactionMeta<A>> {
+        self.transaction_metas.iter().find(|meta| &meta.signature == signature)
+    }
+
+    /// The memos of the transactions of the block by their signature.
+    pub fn memos(&self) -> HashMap<&Signature, &String> {
+        self.transaction_metas.iter()
+            .filter_map(|meta| Some((&meta.signature, meta.memo.as_ref()?)))
+            .collect()
+    }
+
+    fn failed_signatures(&self) -> HashSet<&Signature> {
+        self.transaction_metas.iter()
+            .filter(|meta| !meta.status.is_success())
+            .map(|meta| &meta.signature)
+            .collect()
+    }
+
+    /// The transactions which were executed successfully. Failed transactions only cost their
+    /// fee, their transfers never happened.
+    pub fn successful_transactions(&self) -> impl DoubleEndedIterator<Item = &Transaction<A>> {
+        let failed = self.failed_signatures();
+        self.transactions.iter().filter(move |transaction| !failed.contains(&transaction.signature))
+    }
+
+    /// The lamports moved by the successful system transactions and stake instructions, as
+    /// sender, receiver and amount.
+    pub fn lamport_movements(&self) -> impl DoubleEndedIterator<Item = (&A, &A, u64)> {
+        let failed = self.failed_signatures();
+        let stake_movements = self.stake_instructions.iter()
+            .filter(move |instruction| !failed.contains(&instruction.signature))
+            .filter_map(|instruction| {
+                Some((&instruction.stake_account, instruction.destination.as_ref()?, instruction.amount))
+            });
+        self.successful_transactions()
+            .map(|transaction| (&transaction.sender, &transaction.receiver, transaction.amount))
+            .chain(stake_movements)
+    }
+
+    /// All addresses the block refers to, including duplicates.
+    pub fn addresses(&self) -> Vec<&A> {
+        let mut addresses = Vec::new();
+        let mut invocations = Vec::new();
+        for transaction in &self.transactions {
+            addresses.extend([&transaction.sender, &transaction.receiver]);
+            invocations.push(&transaction.invocation);
+        }
+        for change in &self.balance_changes {
+            addresses.push(&change.address);
+        }
+        for meta in &self.transaction_metas {
+            addresses.push(&meta.fee_payer);
+        }
+        for transfer in &self.token_transfers {
+            addresses.push(&transfer.mint);
+            addresses.extend(transfer.source.iter().chain(&transfer.destination));
+            invocations.push(&transfer.invocation);
+        }
+        for change in &self.token_balance_changes {
+            addresses.extend([&change.account, &change.owner, &change.mint]);
+        }
+        for instruction in &self.raw_instructions {
+            addresses.push(&instruction.program_id);
+            addresses.extend(&instruction.accounts);
+            invocations.push(&instruction.invocation);
+        }
+        for instruction in &self.custom_instructions {
+            addresses.push(&instruction.program_id);
+            invocations.push(&instruction.invocation);
+        }
+        for instruction in &self.stake_instructions {
+            addresses.push(&instruction.stake_account);
+            addresses.extend(instruction.vote_account.iter().chain(&instruction.destination));
+            invocations.push(&instruction.invocation);
+        }
+        for vote in &self.votes {
+            addresses.extend([&vote.vote_account, &vote.authority]);
+            invocations.push(&vote.invocation);
+        }
+        for reward in &self.rewards {
+            addresses.push(&reward.address);
+        }
+        addresses.extend(invocations.into_iter().flatten().map(|invocation| &invocation.program_id));
+        addresses
+    }
+
+    /// The balances of all accounts involved in the block before its first transaction.
+    pub fn pre_balances(&self) -> HashMap<&A, u64> where A: Eq + std::hash::Hash {
+        self.balance_changes.iter()
+            .map(|change| (&change.address, change.pre_balance))
+            .collect()
+    }
+}
+
+impl Block {
+    /// The first address of the block which is not a valid public key, if any.
+    pub fn invalid_address(&self) -> Option<&Address> {
+        self.addresses().into_iter().find(|address| parse_address(address).is_err())
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Account {
     pub address: Address,
-    pub balance: i64,
+    pub balance: u64,
+}
+
+/// The balance of an account together with a summary of the transactions involving it. Only
+/// successful transactions are summed up in `total_sent` and `total_received`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct AccountSummary {
+    pub address: Address,
+    /// `None` if the storage doesn't track a balance for the account.
+    pub balance: Option<u64>,
+    /// The heights of the first and the last block with a transaction involving the account.
+    pub first_seen_block: Option<u64>,
+    pub last_seen_block: Option<u64>,
+    pub total_sent: u64,
+    pub total_received: u64,
+    pub transaction_count: usize,
+    /// The number of distinct other accounts involved in the transactions of the account.
+    pub counterparties: usize,
+}
+
+/// The summed up amount of all token accounts of an owner for one mint.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct TokenBalance {
+    pub mint: Address,
+    pub decimals: u8,
+    pub amount: u64,
+}
+
+/// The activity of a stake account observed in the stored blocks.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct StakeAccount {
+    pub address: Address,
+    /// The validator of the last delegation.
+    pub vote_account: Option<Address>,
+    /// Whether the last delegation was deactivated afterwards.
+    pub deactivated: bool,
+    /// The balance of the account as computed by the accounting mode, if the account is known.
+    pub balance: Option<u64>,
+    /// All the stake instructions involving the account, in the order they were executed.
+    pub instructions: Vec<TransactionWithMeta<StakeInstruction>>,
+}
+
+impl StakeAccount {
+    /// Summarizes the delegation of `address` from its successful `instructions`.
+    pub fn new(
+        address: Address,
+        balance: Option<u64>,
+        instructions: Vec<TransactionWithMeta<StakeInstruction>>,
+    ) -> Self {
+        let mut vote_account = None;
+        let mut deactivated = false;
+        for instruction in &instructions {
+            let failed = instruction.meta.as_ref().is_some_and(|meta| !meta.status.is_success());
+            if failed || instruction.data.stake_account != address {
+                continue;
+            }
+            match instruction.data.kind {
+                StakeInstructionKind::Delegate => {
+                    vote_account = instruction.data.vote_account.clone();
+                    deactivated = false;
+                }
+                StakeInstructionKind::Deactivate => deactivated = true,
+                _ => {}
+            }
+        }
+        Self {address, vote_account, deactivated, balance, instructions}
+    }
+}
+
+/// The activity of a validator observed in the stored blocks.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Validator {
+    pub vote_account: Address,
+    /// The number of successful votes. Every vote earns vote credits once its slot gets rooted.
+    pub votes: u64,
+    pub last_voted_slot: Option<Slot>,
+    /// The timestamp of the block with the last vote.
+    pub last_vote_timestamp: Option<i64>,
+    /// The stake accounts which are currently delegated to the validator and not deactivated.
+    pub stake_accounts: Vec<Address>,
 }